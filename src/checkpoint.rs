@@ -0,0 +1,305 @@
+// Saving a search to carry on with later
+//
+// A checkpoint is a JSON file holding how the search was set up, how far it got,
+// the best layouts found so far, and the layouts the optimiser would carry on
+// from. It also holds fingerprints of the n-grams counted from the corpus and
+// of the weights they're scored with, so a search is only resumed with the
+// same scoring it started with.
+//
+// Only parallel tempering and the island model save the layouts they're
+// searching from. The other optimisers, and searches still in progress on an
+// independent worker, start afresh on resuming. The random numbers of a
+// resumed search come from a new seed, picked by the original seed and how
+// many times it's been resumed.
+
+use std::{
+    hash::{Hash, Hasher},
+    path::Path,
+    time::Duration,
+};
+
+use rand::Rng;
+use serde_json::{json, Value};
+
+use crate::{
+    geometry::{Geometry, KeyPositions},
+    optimise::{worker_rng, Optimiser},
+    Keyboard, Scorer, ALGORITHM_VERSION,
+};
+
+pub struct Checkpoint {
+    pub optimiser: Optimiser,
+    pub threads: usize,
+    pub seed: u64,
+    // How many times the search has been resumed
+    pub resumes: u64,
+    pub template: Keyboard,
+    pub elapsed: Duration,
+    pub evaluations: u64,
+    // The best layouts found, best first
+    pub best: Vec<(i64, Keyboard)>,
+    pub state: Vec<Keyboard>,
+}
+
+// FNV-1a, which unlike the standard library's hasher is sure to give the same
+// fingerprint on every build
+struct Fingerprint(u64);
+
+impl Hasher for Fingerprint {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+pub fn fingerprint(value: &impl Hash) -> u64 {
+    let mut hasher = Fingerprint(0xcbf29ce484222325);
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+// The seed for the workers of a search that's been resumed this many times
+pub fn resumed_seed(seed: u64, resumes: u64) -> u64 {
+    match resumes {
+        0 => seed,
+        _ => worker_rng(seed, resumes as usize).gen(),
+    }
+}
+
+// A custom geometry is saved as the position of each key
+fn keyboard_json(kb: &Keyboard) -> Value {
+    let mut json = json!({
+        "name": kb.name,
+        "keys": kb.to_string(),
+        "geometry": kb.geometry.name(),
+        "pinned": String::from_utf8_lossy(&kb.pinned),
+    });
+
+    if let Geometry::Custom(positions) = &kb.geometry {
+        let positions: Vec<Vec<[f32; 2]>> = positions
+            .iter()
+            .map(|keys| keys.iter().map(|(x, y)| [*x, *y]).collect())
+            .collect();
+        json["positions"] = json!(positions);
+    }
+
+    json
+}
+
+fn field<'a>(value: &'a Value, name: &str) -> Result<&'a Value, String> {
+    value.get(name).ok_or_else(|| format!("missing '{}'", name))
+}
+
+fn string<'a>(value: &'a Value, name: &str) -> Result<&'a str, String> {
+    field(value, name)?
+        .as_str()
+        .ok_or_else(|| format!("'{}' is not a string", name))
+}
+
+fn integer(value: &Value, name: &str) -> Result<u64, String> {
+    field(value, name)?
+        .as_u64()
+        .ok_or_else(|| format!("'{}' is not a whole number", name))
+}
+
+fn number(value: &Value, name: &str) -> Result<f64, String> {
+    field(value, name)?
+        .as_f64()
+        .ok_or_else(|| format!("'{}' is not a number", name))
+}
+
+fn array<'a>(value: &'a Value, name: &str) -> Result<&'a Vec<Value>, String> {
+    field(value, name)?
+        .as_array()
+        .ok_or_else(|| format!("'{}' is not a list", name))
+}
+
+fn keys_rows(keys: &str) -> Result<Vec<Vec<u8>>, String> {
+    if keys.len() != 30 || !keys.is_ascii() {
+        return Err(format!("'{}' is not a layout of 30 keys", keys));
+    }
+
+    Ok(keys.as_bytes().chunks(10).map(|row| row.to_vec()).collect())
+}
+
+// The 30 keys of a layout, on the template's geometry and with its pinned keys.
+// The optimisers only ever swap keys, so it has to hold the same keys as the
+// template, with the pinned ones in the same places.
+fn keys_keyboard(keys: &str, template: &Keyboard) -> Result<Keyboard, String> {
+    let rows = keys_rows(keys)?;
+
+    let sorted = |rows: &[Vec<u8>]| {
+        let mut keys: Vec<u8> = rows.iter().flatten().copied().collect();
+        keys.sort();
+        keys
+    };
+    if sorted(&rows) != sorted(&template.rows) {
+        return Err(format!(
+            "'{}' doesn't hold the same keys as the template '{}'",
+            keys, template
+        ));
+    }
+
+    let kb = Keyboard {
+        rows,
+        ..template.clone()
+    };
+    let (positions, template_positions) = (kb.positions(), template.positions());
+    for key in &template.pinned {
+        if positions[*key as usize] != template_positions[*key as usize] {
+            return Err(format!(
+                "'{}' has moved the pinned key '{}'",
+                keys, *key as char
+            ));
+        }
+    }
+
+    Ok(kb)
+}
+
+fn parse_positions(value: &Value) -> Result<KeyPositions, String> {
+    let mut positions: KeyPositions = [[(0.0, 0.0); 10]; 3];
+    let rows = array(value, "positions")?;
+    if rows.len() != 3 {
+        return Err("'positions' needs 3 rows".into());
+    }
+    for (row, keys) in rows.iter().enumerate() {
+        let keys = keys.as_array().filter(|keys| keys.len() == 10);
+        let Some(keys) = keys else {
+            return Err("each row of 'positions' needs 10 keys".into());
+        };
+        for (col, position) in keys.iter().enumerate() {
+            let x = position.get(0).and_then(|x| x.as_f64());
+            let y = position.get(1).and_then(|y| y.as_f64());
+            let (Some(x), Some(y)) = (x, y) else {
+                return Err("each key of 'positions' needs an x and y".into());
+            };
+            positions[row][col] = (x as f32, y as f32);
+        }
+    }
+
+    Ok(positions)
+}
+
+fn parse_keyboard(value: &Value) -> Result<Keyboard, String> {
+    let geometry = match string(value, "geometry")? {
+        "custom" => Geometry::Custom(Box::new(parse_positions(value)?)),
+        name => Geometry::from_name(name).ok_or_else(|| format!("unknown geometry '{}'", name))?,
+    };
+
+    Ok(Keyboard {
+        name: string(value, "name")?.into(),
+        rows: keys_rows(string(value, "keys")?)?,
+        geometry,
+        pinned: string(value, "pinned")?.into(),
+    })
+}
+
+pub fn save_checkpoint(
+    path: &Path,
+    checkpoint: &Checkpoint,
+    scorer: &Scorer,
+) -> Result<(), String> {
+    let best: Vec<Value> = checkpoint
+        .best
+        .iter()
+        .map(|(score, kb)| json!({ "score": score, "keys": kb.to_string() }))
+        .collect();
+    let state: Vec<String> = checkpoint.state.iter().map(|kb| kb.to_string()).collect();
+
+    let json = json!({
+        "version": ALGORITHM_VERSION,
+        "corpus": format!("{:016x}", scorer.corpus_fingerprint()),
+        "weights": format!("{:016x}", scorer.weights_fingerprint()),
+        "optimiser": checkpoint.optimiser.name(),
+        "threads": checkpoint.threads,
+        "seed": checkpoint.seed,
+        "resumes": checkpoint.resumes,
+        "template": keyboard_json(&checkpoint.template),
+        "elapsed": checkpoint.elapsed.as_secs_f64(),
+        "evaluations": checkpoint.evaluations,
+        "best": best,
+        "state": state,
+    });
+
+    // Write it alongside first, so an interruption can't leave half a checkpoint
+    let partial = path.with_extension("partial");
+    std::fs::write(&partial, format!("{:#}\n", json))
+        .and_then(|_| std::fs::rename(&partial, path))
+        .map_err(|e| format!("{}: couldn't write checkpoint: {}", path.display(), e))
+}
+
+fn parse_checkpoint(source: &str, scorer: &Scorer) -> Result<Checkpoint, String> {
+    let json: Value = serde_json::from_str(source).map_err(|e| format!("invalid JSON: {}", e))?;
+
+    let version = number(&json, "version")?;
+    if version != ALGORITHM_VERSION {
+        return Err(format!(
+            "made with version {} of the algorithm, not {}",
+            version, ALGORITHM_VERSION
+        ));
+    }
+    if string(&json, "corpus")? != format!("{:016x}", scorer.corpus_fingerprint()) {
+        return Err("made with a different corpus".into());
+    }
+    if string(&json, "weights")? != format!("{:016x}", scorer.weights_fingerprint()) {
+        return Err("made with different scoring weights".into());
+    }
+
+    let name = string(&json, "optimiser")?;
+    let optimiser =
+        Optimiser::from_name(name).ok_or_else(|| format!("unknown optimiser '{}'", name))?;
+    let template = parse_keyboard(field(&json, "template")?)?;
+
+    let best = array(&json, "best")?
+        .iter()
+        .map(|layout| {
+            let score = field(layout, "score")?
+                .as_i64()
+                .ok_or("'score' is not a whole number")?;
+            Ok((score, keys_keyboard(string(layout, "keys")?, &template)?))
+        })
+        .collect::<Result<_, String>>()?;
+
+    let state = array(&json, "state")?
+        .iter()
+        .map(|keys| {
+            let keys = keys
+                .as_str()
+                .ok_or("'state' holds a layout that's not a string")?;
+            keys_keyboard(keys, &template)
+        })
+        .collect::<Result<_, String>>()?;
+
+    let threads = integer(&json, "threads")? as usize;
+    if threads == 0 {
+        return Err("'threads' needs to be at least 1".into());
+    }
+
+    Ok(Checkpoint {
+        optimiser,
+        threads,
+        seed: integer(&json, "seed")?,
+        resumes: integer(&json, "resumes")?,
+        template,
+        elapsed: Duration::try_from_secs_f64(number(&json, "elapsed")?)
+            .map_err(|_| "'elapsed' is not a length of time")?,
+        evaluations: integer(&json, "evaluations")?,
+        best,
+        state,
+    })
+}
+
+// Load a checkpoint, refusing one made with a different corpus or weights
+pub fn load_checkpoint(path: &Path, scorer: &Scorer) -> Result<Checkpoint, String> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|e| format!("{}: couldn't read checkpoint: {}", path.display(), e))?;
+
+    parse_checkpoint(&source, scorer).map_err(|e| format!("{}: {}", path.display(), e))
+}
@@ -0,0 +1,101 @@
+// The full boards that the 30 optimised keys are placed on when exporting
+// firmware keymaps, chosen by the geometry of the layout
+
+use crate::{export::US_EXTRA_KEYS, geometry::Geometry};
+
+pub enum Slot {
+    // One of the optimised keys, by (row, col)
+    Key(usize, usize),
+    // One of the keys of the US layout outside the 30, by its index in US_EXTRA_KEYS
+    Extra(usize),
+    // A key that stays the same whatever the layout, as its QMK keycode and ZMK binding
+    Fixed(&'static str, &'static str),
+}
+
+use Slot::{Extra, Fixed, Key};
+
+pub struct Board {
+    // The QMK layout macro the keys are listed in
+    pub qmk_layout: &'static str,
+    pub rows: Vec<Vec<Slot>>,
+}
+
+const NONE: Slot = Fixed("KC_NO", "&none");
+const ESC: Slot = Fixed("KC_ESC", "&kp ESC");
+const TAB: Slot = Fixed("KC_TAB", "&kp TAB");
+const CAPS: Slot = Fixed("KC_CAPS", "&kp CAPS");
+const BSPC: Slot = Fixed("KC_BSPC", "&kp BSPC");
+const ENT: Slot = Fixed("KC_ENT", "&kp RET");
+const SPC: Slot = Fixed("KC_SPC", "&kp SPACE");
+const LSFT: Slot = Fixed("KC_LSFT", "&kp LSHFT");
+const RSFT: Slot = Fixed("KC_RSFT", "&kp RSHFT");
+const LCTL: Slot = Fixed("KC_LCTL", "&kp LCTRL");
+const RCTL: Slot = Fixed("KC_RCTL", "&kp RCTRL");
+const LGUI: Slot = Fixed("KC_LGUI", "&kp LGUI");
+const RGUI: Slot = Fixed("KC_RGUI", "&kp RGUI");
+const LALT: Slot = Fixed("KC_LALT", "&kp LALT");
+const RALT: Slot = Fixed("KC_RALT", "&kp RALT");
+
+// The extra key that types this character on the US layout
+fn extra(key: u8) -> Slot {
+    Extra(US_EXTRA_KEYS.iter().position(|k| *k == key).unwrap())
+}
+
+fn keys(row: usize) -> impl Iterator<Item = Slot> {
+    (0..10).map(move |col| Key(row, col))
+}
+
+fn row(before: Vec<Slot>, row: usize, after: Vec<Slot>) -> Vec<Slot> {
+    before.into_iter().chain(keys(row)).chain(after).collect()
+}
+
+impl Board {
+    pub fn for_geometry(geometry: &Geometry) -> Board {
+        match geometry.standard() {
+            Geometry::Ansi => Board {
+                qmk_layout: "LAYOUT_60_ansi",
+                rows: vec![
+                    b"`1234567890-="
+                        .iter()
+                        .map(|key| extra(*key))
+                        .chain([BSPC])
+                        .collect(),
+                    row(vec![TAB], 0, vec![extra(b'['), extra(b']'), extra(b'\\')]),
+                    row(vec![CAPS], 1, vec![extra(b'\''), ENT]),
+                    row(vec![LSFT], 2, vec![RSFT]),
+                    vec![
+                        LCTL,
+                        LGUI,
+                        LALT,
+                        SPC,
+                        RALT,
+                        RGUI,
+                        Fixed("KC_APP", "&kp K_APP"),
+                        RCTL,
+                    ],
+                ],
+            },
+            Geometry::Ortho => Board {
+                qmk_layout: "LAYOUT_ortho_4x12",
+                rows: vec![
+                    row(vec![TAB], 0, vec![BSPC]),
+                    row(vec![ESC], 1, vec![extra(b'\'')]),
+                    row(vec![LSFT], 2, vec![ENT]),
+                    vec![
+                        LCTL, LGUI, LALT, NONE, NONE, SPC, SPC, NONE, NONE, RALT, RGUI, RCTL,
+                    ],
+                ],
+            },
+            // standard() never gives a custom geometry
+            Geometry::ColumnStagger | Geometry::Custom(_) => Board {
+                qmk_layout: "LAYOUT_split_3x5_3",
+                rows: vec![
+                    keys(0).collect(),
+                    keys(1).collect(),
+                    keys(2).collect(),
+                    vec![LGUI, SPC, LSFT, RSFT, BSPC, ENT],
+                ],
+            },
+        }
+    }
+}
@@ -0,0 +1,56 @@
+// kanata configuration files, for remapping a qwerty board on Linux, macOS and
+// Windows
+//
+// Save the output as <name>.kbd and run `kanata --cfg <name>.kbd`. The remapping
+// lasts until kanata is stopped.
+
+use std::fmt::Write;
+
+use crate::{export::qwerty_remapping, Keyboard};
+
+fn key_name(key: u8) -> String {
+    match key {
+        b'`' => "grv".into(),
+        _ => (key as char).to_string(),
+    }
+}
+
+pub fn export_kanata(kb: &Keyboard) -> String {
+    let remapping = qwerty_remapping(kb);
+
+    // The 30 keys, and any other key that changes
+    let keys: Vec<&(u8, Option<u8>)> = remapping
+        .iter()
+        .enumerate()
+        .filter(|(idx, (from, to))| *idx < 30 || *to != Some(*from))
+        .map(|(_, remap)| remap)
+        .collect();
+
+    let mut output = String::new();
+
+    let _ = writeln!(output, ";; {}: generated from {}", kb.name, kb);
+    let _ = writeln!(
+        output,
+        ";; Remaps a qwerty board, with the US layout set in the OS"
+    );
+    let _ = writeln!(output, "(defcfg");
+    let _ = writeln!(output, "  process-unmapped-keys yes");
+    let _ = writeln!(output, ")");
+    let _ = writeln!(output);
+
+    let mut src = String::new();
+    let mut layer = String::new();
+
+    for (idx, (from, to)) in keys.iter().enumerate() {
+        let separator = if idx % 10 == 0 { "\n  " } else { " " };
+        let to = to.map(key_name).unwrap_or_else(|| "XX".into());
+        let _ = write!(src, "{}{}", separator, key_name(*from));
+        let _ = write!(layer, "{}{}", separator, to);
+    }
+
+    let _ = writeln!(output, "(defsrc{}\n)", src);
+    let _ = writeln!(output);
+    let _ = writeln!(output, "(deflayer {}{}\n)", kb.name, layer);
+
+    output
+}
@@ -0,0 +1,52 @@
+// keyd configuration files, for remapping a qwerty board on Linux
+//
+// Save the output as /etc/keyd/<name>.conf and run `sudo keyd reload`. Remove
+// the file and reload again to go back to qwerty.
+
+use std::fmt::Write;
+
+use crate::{export::qwerty_remapping, Keyboard};
+
+fn key_name(key: u8) -> String {
+    let name = match key {
+        b'a'..=b'z' | b'0'..=b'9' => return (key as char).to_string(),
+        b';' => "semicolon",
+        b'\'' => "apostrophe",
+        b',' => "comma",
+        b'.' => "dot",
+        b'/' => "slash",
+        b'-' => "minus",
+        b'=' => "equal",
+        b'[' => "leftbrace",
+        b']' => "rightbrace",
+        b'\\' => "backslash",
+        b'`' => "grave",
+        _ => "noop",
+    };
+    name.into()
+}
+
+pub fn export_keyd(kb: &Keyboard) -> String {
+    let mut output = String::new();
+
+    let _ = writeln!(output, "# {}: generated from {}", kb.name, kb);
+    let _ = writeln!(
+        output,
+        "# Remaps a qwerty board, with the US layout set in the OS"
+    );
+    let _ = writeln!(output, "[ids]");
+    let _ = writeln!(output, "*");
+    let _ = writeln!(output);
+    let _ = writeln!(output, "[main]");
+
+    for (from, to) in qwerty_remapping(kb) {
+        if to == Some(from) {
+            continue;
+        }
+
+        let to = to.map(key_name).unwrap_or_else(|| "noop".into());
+        let _ = writeln!(output, "{} = {}", key_name(from), to);
+    }
+
+    output
+}
@@ -0,0 +1,137 @@
+// Apple .keylayout files, for macOS
+//
+// Copy the file into ~/Library/Keyboard Layouts, log out and back in, and add
+// the layout under Input Sources in the keyboard settings.
+
+use std::fmt::Write;
+
+use crate::{
+    export::{fill_extra_keys, fill_keys, shifted, US_EXTRA_KEYS},
+    Keyboard,
+};
+
+// Virtual key codes of the 30 keys we lay out, by row
+const KEY_CODES: [[u8; 10]; 3] = [
+    [12, 13, 14, 15, 17, 16, 32, 34, 31, 35],
+    [0, 1, 2, 3, 5, 4, 38, 40, 37, 41],
+    [6, 7, 8, 9, 11, 45, 46, 43, 47, 44],
+];
+
+// Virtual key codes of US_EXTRA_KEYS
+const EXTRA_KEY_CODES: [u8; US_EXTRA_KEYS.len()] = [
+    50, 18, 19, 20, 21, 23, 22, 26, 28, 25, 29, 27, 24, 33, 30, 42, 39,
+];
+
+// Keys that type control characters, which are the same on every layout
+const CONTROL_KEYS: [(u8, &str); 9] = [
+    (36, "&#x000D;"),
+    (48, "&#x0009;"),
+    (49, " "),
+    (51, "&#x0008;"),
+    (53, "&#x001B;"),
+    (123, "&#x001C;"),
+    (124, "&#x001D;"),
+    (125, "&#x001F;"),
+    (126, "&#x001E;"),
+];
+
+fn xml_escape(key: char) -> String {
+    match key {
+        '&' => "&#x0026;".into(),
+        '<' => "&#x003C;".into(),
+        '>' => "&#x003E;".into(),
+        '"' => "&#x0022;".into(),
+        '\'' => "&#x0027;".into(),
+        _ => key.to_string(),
+    }
+}
+
+pub fn export_keylayout(kb: &Keyboard) -> String {
+    let rows = fill_keys(kb, &Keyboard::qwerty());
+    let extras = fill_extra_keys(&rows);
+
+    let mut keys: Vec<(u8, u8)> = vec![];
+    for (row, row_keys) in rows.iter().enumerate() {
+        for (col, key) in row_keys.iter().enumerate() {
+            keys.push((KEY_CODES[row][col], *key));
+        }
+    }
+    for (code, key) in EXTRA_KEY_CODES.iter().zip(&extras) {
+        if let Some(key) = key {
+            keys.push((*code, *key));
+        }
+    }
+    keys.sort();
+
+    // Layouts need a unique negative id, so derive one from the name
+    let id = -1
+        - kb.name
+            .bytes()
+            .fold(0i32, |acc, b| (acc * 31 + b as i32) % 30000);
+
+    let mut output = String::new();
+
+    let _ = writeln!(output, "<?xml version=\"1.1\" encoding=\"UTF-8\"?>");
+    let _ = writeln!(
+        output,
+        "<!DOCTYPE keyboard SYSTEM \"file://localhost/System/Library/DTDs/KeyboardLayout.dtd\">"
+    );
+    let _ = writeln!(
+        output,
+        "<keyboard group=\"126\" id=\"{}\" name=\"{}\" maxout=\"1\">",
+        id,
+        kb.name.chars().map(xml_escape).collect::<String>()
+    );
+    let _ = writeln!(output, "    <layouts>");
+    let _ = writeln!(
+        output,
+        "        <layout first=\"0\" last=\"17\" modifiers=\"Modifiers\" mapSet=\"ANSI\"/>"
+    );
+    let _ = writeln!(output, "    </layouts>");
+    let _ = writeln!(
+        output,
+        "    <modifierMap id=\"Modifiers\" defaultIndex=\"0\">"
+    );
+    let _ = writeln!(output, "        <keyMapSelect mapIndex=\"0\">");
+    let _ = writeln!(output, "            <modifier keys=\"command?\"/>");
+    let _ = writeln!(output, "        </keyMapSelect>");
+    let _ = writeln!(output, "        <keyMapSelect mapIndex=\"1\">");
+    let _ = writeln!(
+        output,
+        "            <modifier keys=\"anyShift caps? command?\"/>"
+    );
+    let _ = writeln!(output, "        </keyMapSelect>");
+    let _ = writeln!(output, "        <keyMapSelect mapIndex=\"2\">");
+    let _ = writeln!(output, "            <modifier keys=\"caps command?\"/>");
+    let _ = writeln!(output, "        </keyMapSelect>");
+    let _ = writeln!(output, "    </modifierMap>");
+    let _ = writeln!(output, "    <keyMapSet id=\"ANSI\">");
+
+    // Unshifted, shifted, and caps lock which only affects letters
+    let maps: [fn(u8) -> u8; 3] = [|key| key, shifted, |key| key.to_ascii_uppercase()];
+
+    for (index, map) in maps.iter().enumerate() {
+        let _ = writeln!(output, "        <keyMap index=\"{}\">", index);
+        for (code, key) in &keys {
+            let _ = writeln!(
+                output,
+                "            <key code=\"{}\" output=\"{}\"/>",
+                code,
+                xml_escape(map(*key) as char)
+            );
+        }
+        for (code, control) in CONTROL_KEYS {
+            let _ = writeln!(
+                output,
+                "            <key code=\"{}\" output=\"{}\"/>",
+                code, control
+            );
+        }
+        let _ = writeln!(output, "        </keyMap>");
+    }
+
+    let _ = writeln!(output, "    </keyMapSet>");
+    let _ = writeln!(output, "</keyboard>");
+
+    output
+}
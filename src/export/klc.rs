@@ -0,0 +1,185 @@
+// Microsoft Keyboard Layout Creator .klc files, for Windows
+//
+// MSKLC expects the file to be UTF-16, so convert it first, eg) with
+// `iconv -f UTF-8 -t UTF-16 layout.klc`. Open it in MSKLC and build the
+// setup package from there.
+
+use std::fmt::Write;
+
+use crate::{
+    export::{fill_extra_keys, fill_keys, shifted, US_EXTRA_KEYS},
+    Keyboard,
+};
+
+// Scan codes of the 30 keys we lay out, by row
+const SCAN_CODES: [[u8; 10]; 3] = [
+    [0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19],
+    [0x1e, 0x1f, 0x20, 0x21, 0x22, 0x23, 0x24, 0x25, 0x26, 0x27],
+    [0x2c, 0x2d, 0x2e, 0x2f, 0x30, 0x31, 0x32, 0x33, 0x34, 0x35],
+];
+
+// Scan codes of US_EXTRA_KEYS
+const EXTRA_SCAN_CODES: [u8; US_EXTRA_KEYS.len()] = [
+    0x29, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x1a, 0x1b, 0x2b,
+    0x28,
+];
+
+// The virtual key of a character on the US layout
+fn virtual_key(key: u8) -> Option<String> {
+    let name = match key {
+        b'a'..=b'z' => return Some((key.to_ascii_uppercase() as char).to_string()),
+        b'0'..=b'9' => return Some((key as char).to_string()),
+        b';' => "OEM_1",
+        b'/' => "OEM_2",
+        b'`' => "OEM_3",
+        b'[' => "OEM_4",
+        b'\\' => "OEM_5",
+        b']' => "OEM_6",
+        b'\'' => "OEM_7",
+        b',' => "OEM_COMMA",
+        b'.' => "OEM_PERIOD",
+        b'-' => "OEM_MINUS",
+        b'=' => "OEM_PLUS",
+        _ => return None,
+    };
+    Some(name.into())
+}
+
+// The virtual key for each of the keys, which have to be different. A character
+// that's not on the US layout takes one that's spare, either from a character
+// the layout leaves out or one the US layout doesn't use.
+fn virtual_keys(keys: &[u8]) -> Vec<String> {
+    let qwerty = Keyboard::qwerty();
+    let mut spare = qwerty
+        .rows
+        .iter()
+        .flatten()
+        .chain(US_EXTRA_KEYS.iter())
+        .filter(|key| !keys.contains(key))
+        .filter_map(|key| virtual_key(*key))
+        .chain(["OEM_8".to_string(), "OEM_102".to_string()]);
+
+    // There are more virtual keys than keys that type a character, so there's
+    // always one spare
+    keys.iter()
+        .map(|key| virtual_key(*key).unwrap_or_else(|| spare.next().unwrap()))
+        .collect()
+}
+
+// Letters and digits are written as they are, everything else as its code point
+fn character(key: u8) -> String {
+    if key.is_ascii_alphanumeric() {
+        (key as char).to_string()
+    } else {
+        format!("{:04x}", key)
+    }
+}
+
+fn layout_line(output: &mut String, scan_code: u8, virtual_key: &str, key: u8) {
+    let caps = if key.is_ascii_alphabetic() { 1 } else { 0 };
+
+    let _ = writeln!(
+        output,
+        "{:02x}\t{}\t\t{}\t{}\t{}",
+        scan_code,
+        virtual_key,
+        caps,
+        character(key),
+        character(shifted(key))
+    );
+}
+
+pub fn export_klc(kb: &Keyboard) -> String {
+    let rows = fill_keys(kb, &Keyboard::qwerty());
+    let extras = fill_extra_keys(&rows);
+
+    // Layout names are limited to 8 characters
+    let short_name: String = kb
+        .name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .take(8)
+        .collect();
+
+    let mut output = String::new();
+
+    let _ = writeln!(output, "KBD\t{}\t\"{}\"", short_name, kb.name);
+    let _ = writeln!(output);
+    let _ = writeln!(output, "COPYRIGHT\t\"Generated from {}\"", kb);
+    let _ = writeln!(output);
+    let _ = writeln!(output, "COMPANY\t\"\"");
+    let _ = writeln!(output);
+    let _ = writeln!(output, "LOCALENAME\t\"en-US\"");
+    let _ = writeln!(output);
+    let _ = writeln!(output, "LOCALEID\t\"00000409\"");
+    let _ = writeln!(output);
+    let _ = writeln!(output, "VERSION\t1.0");
+    let _ = writeln!(output);
+    let _ = writeln!(output, "SHIFTSTATE");
+    let _ = writeln!(output);
+    let _ = writeln!(output, "0\t//Column 4");
+    let _ = writeln!(output, "1\t//Column 5 : Shft");
+    let _ = writeln!(output);
+    let _ = writeln!(output, "LAYOUT");
+    let _ = writeln!(output);
+    let _ = writeln!(output, "//SC\tVK_\t\tCap\t0\t1");
+    let _ = writeln!(output, "//--\t----\t\t----\t----\t----");
+    let _ = writeln!(output);
+
+    let keys: Vec<(u8, u8)> = EXTRA_SCAN_CODES
+        .iter()
+        .zip(&extras)
+        .filter_map(|(scan_code, key)| key.map(|key| (*scan_code, key)))
+        .chain(
+            SCAN_CODES
+                .iter()
+                .flatten()
+                .zip(rows.iter().flatten())
+                .map(|(scan_code, key)| (*scan_code, *key)),
+        )
+        .collect();
+    let virtual_keys = virtual_keys(&keys.iter().map(|(_, key)| *key).collect::<Vec<_>>());
+
+    for ((scan_code, key), virtual_key) in keys.iter().zip(&virtual_keys) {
+        layout_line(&mut output, *scan_code, virtual_key, *key);
+    }
+    let _ = writeln!(output, "39\tSPACE\t\t0\t0020\t0020");
+    let _ = writeln!(output, "53\tDECIMAL\t\t0\t002e\t002e");
+
+    let _ = writeln!(output);
+    let _ = writeln!(output, "DESCRIPTIONS");
+    let _ = writeln!(output);
+    let _ = writeln!(output, "0409\t{}", kb.name);
+    let _ = writeln!(output);
+    let _ = writeln!(output, "LANGUAGENAMES");
+    let _ = writeln!(output);
+    let _ = writeln!(output, "0409\tEnglish (United States)");
+    let _ = writeln!(output);
+    let _ = writeln!(output, "ENDKBD");
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn virtual_keys_are_unique() {
+        for kb in Keyboard::standard_layouts() {
+            let output = export_klc(&kb);
+            let mut virtual_keys: Vec<&str> = output
+                .lines()
+                .skip_while(|line| *line != "LAYOUT")
+                .take_while(|line| *line != "DESCRIPTIONS")
+                .filter(|line| !line.is_empty() && !line.starts_with("//"))
+                .filter_map(|line| line.split('\t').nth(1))
+                .collect();
+            let count = virtual_keys.len();
+            virtual_keys.sort();
+            virtual_keys.dedup();
+
+            assert_eq!(virtual_keys.len(), count, "{}", kb);
+        }
+    }
+}
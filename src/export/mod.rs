@@ -0,0 +1,300 @@
+// Turning a layout into files that other tools can install or load
+
+mod board;
+mod kanata;
+mod keyd;
+mod keylayout;
+mod klc;
+mod qmk;
+mod xkb;
+mod zmk;
+
+use crate::{kle, Keyboard};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    Xkb,
+    Qmk,
+    Zmk,
+    Klc,
+    Keylayout,
+    Kle,
+    Keyd,
+    Kanata,
+}
+
+impl Format {
+    pub const ALL: [Format; 8] = [
+        Format::Xkb,
+        Format::Qmk,
+        Format::Zmk,
+        Format::Klc,
+        Format::Keylayout,
+        Format::Kle,
+        Format::Keyd,
+        Format::Kanata,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::Xkb => "xkb",
+            Format::Qmk => "qmk",
+            Format::Zmk => "zmk",
+            Format::Klc => "klc",
+            Format::Keylayout => "keylayout",
+            Format::Kle => "kle",
+            Format::Keyd => "keyd",
+            Format::Kanata => "kanata",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Format> {
+        Format::ALL
+            .iter()
+            .find(|format| format.name() == name)
+            .copied()
+    }
+}
+
+pub fn export(kb: &Keyboard, format: Format) -> String {
+    match format {
+        Format::Xkb => xkb::export_xkb(kb),
+        Format::Qmk => qmk::export_qmk(kb),
+        Format::Zmk => zmk::export_zmk(kb),
+        Format::Klc => klc::export_klc(kb),
+        Format::Keylayout => keylayout::export_keylayout(kb),
+        Format::Kle => kle::export_kle(kb),
+        Format::Keyd => keyd::export_keyd(kb),
+        Format::Kanata => kanata::export_kanata(kb),
+    }
+}
+
+// The keys to put on the board, with every filler key on the layout replaced by
+// a key from the base layout that the layout doesn't place anywhere else. A
+// filler that sits where such a key already was keeps it.
+pub fn fill_keys(kb: &Keyboard, base: &Keyboard) -> Vec<Vec<u8>> {
+    let placed: Vec<u8> = kb.rows.iter().flatten().copied().collect();
+    let mut displaced: Vec<u8> = base
+        .rows
+        .iter()
+        .flatten()
+        .copied()
+        .filter(|key| *key != b'_' && !placed.contains(key))
+        .collect();
+
+    let mut rows = kb.rows.clone();
+
+    for (row, keys) in rows.iter_mut().enumerate() {
+        for (col, key) in keys.iter_mut().enumerate() {
+            let base_key = base.rows[row][col];
+
+            if *key == b'_' && displaced.contains(&base_key) {
+                *key = base_key;
+                displaced.retain(|k| *k != base_key);
+            }
+        }
+    }
+
+    let mut displaced = displaced.into_iter();
+    for key in rows.iter_mut().flatten() {
+        if *key == b'_' {
+            if let Some(base_key) = displaced.next() {
+                *key = base_key;
+            }
+        }
+    }
+
+    rows
+}
+
+// The layout with each of the given characters back on the key that types it
+// on the US layout, and off any other key it was on. Where that's one of the 30
+// keys, the layout needs to leave it empty with a filler.
+pub fn keep_keys(kb: &Keyboard, keep: &str) -> Result<Keyboard, String> {
+    let qwerty = Keyboard::qwerty();
+    let mut rows = kb.rows.clone();
+
+    for key in keep.chars() {
+        let on_us_layout = key.is_ascii()
+            && (qwerty.rows.iter().flatten().any(|k| *k == key as u8)
+                || US_EXTRA_KEYS.contains(&(key as u8)));
+        if !on_us_layout || key.is_ascii_alphabetic() {
+            return Err(format!(
+                "can't keep '{}', only the keys of the US layout other than letters can be kept",
+                key
+            ));
+        }
+        let key = key as u8;
+
+        for placed in rows.iter_mut().flatten() {
+            if *placed == key {
+                *placed = b'_';
+            }
+        }
+
+        for (row, keys) in qwerty.rows.iter().enumerate() {
+            for (col, _) in keys.iter().enumerate().filter(|(_, k)| **k == key) {
+                match rows[row][col] {
+                    b'_' => rows[row][col] = key,
+                    other => {
+                        return Err(format!(
+                            "can't keep '{}' on its key, the layout puts '{}' there",
+                            key as char, other as char
+                        ))
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(Keyboard { rows, ..kb.clone() })
+}
+
+// The character typed with shift held, following the US layout
+pub fn shifted(key: u8) -> u8 {
+    match key {
+        b'a'..=b'z' => key.to_ascii_uppercase(),
+        b';' => b':',
+        b'\'' => b'"',
+        b',' => b'<',
+        b'.' => b'>',
+        b'/' => b'?',
+        b'-' => b'_',
+        b'=' => b'+',
+        b'[' => b'{',
+        b']' => b'}',
+        b'\\' => b'|',
+        b'`' => b'~',
+        b'1' => b'!',
+        b'2' => b'@',
+        b'3' => b'#',
+        b'4' => b'$',
+        b'5' => b'%',
+        b'6' => b'^',
+        b'7' => b'&',
+        b'8' => b'*',
+        b'9' => b'(',
+        b'0' => b')',
+        _ => key,
+    }
+}
+
+// The keys of the US layout outside of the 30 we lay out, by the character they type
+pub const US_EXTRA_KEYS: [u8; 17] = *b"`1234567890-=[]\\'";
+
+// The character for each of US_EXTRA_KEYS, given the filled in rows of the layout.
+// Any character the layout has moved onto its own keys is replaced by one that
+// is left over, so nothing is typed by two keys, or dropped if none are left.
+pub fn fill_extra_keys(rows: &[Vec<u8>]) -> Vec<Option<u8>> {
+    let placed: Vec<u8> = rows.iter().flatten().copied().collect();
+    let qwerty = Keyboard::qwerty();
+
+    let mut left_over = qwerty
+        .rows
+        .iter()
+        .flatten()
+        .chain(US_EXTRA_KEYS.iter())
+        .copied()
+        .filter(|key| !placed.contains(key) && !US_EXTRA_KEYS.contains(key))
+        .collect::<Vec<_>>()
+        .into_iter();
+
+    US_EXTRA_KEYS
+        .iter()
+        .map(|key| {
+            if placed.contains(key) {
+                left_over.next()
+            } else {
+                Some(*key)
+            }
+        })
+        .collect()
+}
+
+// For remapping a physical US qwerty board in software: each key of the board
+// by the character it types on qwerty, with the character it should type for
+// the layout instead, or None if it should type nothing
+pub fn qwerty_remapping(kb: &Keyboard) -> Vec<(u8, Option<u8>)> {
+    let qwerty = Keyboard::qwerty();
+    let rows = fill_keys(kb, &qwerty);
+    let extras = fill_extra_keys(&rows);
+
+    let keys = qwerty
+        .rows
+        .iter()
+        .flatten()
+        .zip(rows.iter().flatten())
+        .map(|(from, to)| (*from, Some(*to)));
+
+    keys.chain(US_EXTRA_KEYS.iter().copied().zip(extras))
+        .collect()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::geometry::Geometry;
+
+    // How many times the code is written in the output as a whole
+    fn count(output: &str, code: &str) -> usize {
+        output
+            .match_indices(code)
+            .filter(|(idx, _)| {
+                let next = output[idx + code.len()..].chars().next();
+                !next.is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+            })
+            .count()
+    }
+
+    // Every character the layout types is on exactly one key of the board, with
+    // each written as the given code for it
+    pub fn check_every_key_once(format: Format, code: fn(u8) -> String) {
+        let us_keys: Vec<u8> = Keyboard::qwerty()
+            .rows
+            .iter()
+            .flatten()
+            .chain(US_EXTRA_KEYS.iter())
+            .copied()
+            .collect();
+
+        let mut layouts = Keyboard::standard_layouts();
+        layouts.push(keep_keys(&Keyboard::graphite(), "'-").unwrap());
+
+        for kb in layouts {
+            for geometry in Geometry::ALL {
+                let kb = Keyboard {
+                    geometry,
+                    ..kb.clone()
+                };
+                let output = export(&kb, format);
+
+                let rows = fill_keys(&kb, &Keyboard::qwerty());
+                for key in rows.iter().flatten() {
+                    assert_eq!(
+                        count(&output, &code(*key)),
+                        1,
+                        "'{}' in {}",
+                        *key as char,
+                        kb
+                    );
+                }
+
+                // A full board has room for everything the US layout types,
+                // unless the layout takes up keys with characters it doesn't
+                if kb.geometry == Geometry::Ansi
+                    && rows.iter().flatten().all(|k| us_keys.contains(k))
+                {
+                    for key in &us_keys {
+                        assert_eq!(
+                            count(&output, &code(*key)),
+                            1,
+                            "'{}' in {}",
+                            *key as char,
+                            kb
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
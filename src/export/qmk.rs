@@ -0,0 +1,98 @@
+// QMK keymap.c files, holding the layout as the base layer
+//
+// Replace the keymaps array in your board's keymap.c with this one. The keys
+// around the 30 we lay out come from a standard board for the layout's geometry,
+// with the punctuation the layout displaced moved onto them.
+
+use std::fmt::Write;
+
+use crate::{
+    export::{
+        board::{Board, Slot},
+        fill_extra_keys, fill_keys,
+    },
+    Keyboard,
+};
+
+fn keycode(key: u8) -> String {
+    let name = match key {
+        b'a'..=b'z' => return format!("KC_{}", key.to_ascii_uppercase() as char),
+        b'0'..=b'9' => return format!("KC_{}", key as char),
+        b';' => "KC_SCLN",
+        b':' => "KC_COLN",
+        b'\'' => "KC_QUOT",
+        b'"' => "KC_DQUO",
+        b',' => "KC_COMM",
+        b'<' => "KC_LABK",
+        b'.' => "KC_DOT",
+        b'>' => "KC_RABK",
+        b'/' => "KC_SLSH",
+        b'?' => "KC_QUES",
+        b'-' => "KC_MINS",
+        b'_' => "KC_UNDS",
+        b'=' => "KC_EQL",
+        b'+' => "KC_PLUS",
+        b'[' => "KC_LBRC",
+        b'{' => "KC_LCBR",
+        b']' => "KC_RBRC",
+        b'}' => "KC_RCBR",
+        b'\\' => "KC_BSLS",
+        b'|' => "KC_PIPE",
+        b'`' => "KC_GRV",
+        b'~' => "KC_TILD",
+        b'!' => "KC_EXLM",
+        _ => "KC_NO",
+    };
+    name.into()
+}
+
+pub fn export_qmk(kb: &Keyboard) -> String {
+    let keys = fill_keys(kb, &Keyboard::qwerty());
+    let extras = fill_extra_keys(&keys);
+    let board = Board::for_geometry(&kb.geometry);
+    let mut output = String::new();
+
+    let _ = writeln!(output, "// {}: generated from {}", kb.name, kb);
+    let _ = writeln!(output, "#include QMK_KEYBOARD_H");
+    let _ = writeln!(output);
+    let _ = writeln!(
+        output,
+        "const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {{"
+    );
+    let _ = writeln!(output, "    [0] = {}(", board.qmk_layout);
+
+    for (idx, row) in board.rows.iter().enumerate() {
+        let codes: Vec<_> = row
+            .iter()
+            .map(|slot| match slot {
+                Slot::Key(row, col) => keycode(keys[*row][*col]),
+                Slot::Fixed(qmk, _) => qmk.to_string(),
+                Slot::Extra(idx) => extras[*idx].map(keycode).unwrap_or_else(|| "KC_NO".into()),
+            })
+            .map(|code| format!("{:<9}", code + ","))
+            .collect();
+
+        let mut line = codes.concat();
+        if idx == board.rows.len() - 1 {
+            // No comma after the last key
+            line = line.trim_end().trim_end_matches(',').to_string();
+        }
+        let _ = writeln!(output, "        {}", line.trim_end());
+    }
+
+    let _ = writeln!(output, "    )");
+    let _ = writeln!(output, "}};");
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{tests::check_every_key_once, Format};
+
+    #[test]
+    fn every_key_appears_once() {
+        check_every_key_once(Format::Qmk, keycode);
+    }
+}
@@ -0,0 +1,106 @@
+// XKB symbols files, for X11 and most Wayland compositors
+//
+// Save the output as /usr/share/X11/xkb/symbols/<name> and switch to it with
+// `setxkbmap <name>`.
+
+use std::fmt::Write;
+
+use crate::{
+    export::{fill_extra_keys, fill_keys, shifted},
+    Keyboard,
+};
+
+const KEY_CODES: [&str; 3] = ["AD", "AC", "AB"];
+
+// The key codes of US_EXTRA_KEYS
+const EXTRA_KEY_CODES: [&str; 17] = [
+    "TLDE", "AE01", "AE02", "AE03", "AE04", "AE05", "AE06", "AE07", "AE08", "AE09", "AE10", "AE11",
+    "AE12", "AD11", "AD12", "BKSL", "AC11",
+];
+
+fn keysym(key: u8) -> String {
+    let name = match key {
+        b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' => return (key as char).to_string(),
+        b';' => "semicolon",
+        b':' => "colon",
+        b'\'' => "apostrophe",
+        b'"' => "quotedbl",
+        b',' => "comma",
+        b'<' => "less",
+        b'.' => "period",
+        b'>' => "greater",
+        b'/' => "slash",
+        b'?' => "question",
+        b'-' => "minus",
+        b'_' => "underscore",
+        b'=' => "equal",
+        b'+' => "plus",
+        b'[' => "bracketleft",
+        b'{' => "braceleft",
+        b']' => "bracketright",
+        b'}' => "braceright",
+        b'\\' => "backslash",
+        b'|' => "bar",
+        b'`' => "grave",
+        b'~' => "asciitilde",
+        b'!' => "exclam",
+        b'@' => "at",
+        b'#' => "numbersign",
+        b'$' => "dollar",
+        b'%' => "percent",
+        b'^' => "asciicircum",
+        b'&' => "ampersand",
+        b'*' => "asterisk",
+        b'(' => "parenleft",
+        b')' => "parenright",
+        _ => return format!("U{:04X}", key),
+    };
+    name.into()
+}
+
+fn write_key(output: &mut String, code: &str, key: Option<u8>) {
+    let (normal, shift) = match key {
+        Some(key) => (keysym(key), keysym(shifted(key))),
+        None => ("NoSymbol".into(), "NoSymbol".into()),
+    };
+    let _ = writeln!(
+        output,
+        "    key <{}> {{ [ {}, {} ] }};",
+        code, normal, shift
+    );
+}
+
+pub fn export_xkb(kb: &Keyboard) -> String {
+    let rows = fill_keys(kb, &Keyboard::qwerty());
+    let extras = fill_extra_keys(&rows);
+    let mut output = String::new();
+
+    let _ = writeln!(output, "// {}: generated from {}", kb.name, kb);
+    let _ = writeln!(
+        output,
+        "// Keys not listed here are the same as the US layout"
+    );
+    let _ = writeln!(output, "default partial alphanumeric_keys");
+    let _ = writeln!(output, "xkb_symbols \"basic\" {{");
+    let _ = writeln!(output, "    include \"us(basic)\"");
+    let _ = writeln!(output, "    name[Group1] = \"English ({})\";", kb.name);
+
+    for (row, keys) in rows.iter().enumerate() {
+        let _ = writeln!(output);
+        for (col, key) in keys.iter().enumerate() {
+            let code = format!("{}{:02}", KEY_CODES[row], col + 1);
+            write_key(&mut output, &code, Some(*key));
+        }
+    }
+
+    // Written out too, so a character the layout has moved onto the 30 keys
+    // isn't left on its US key as well
+    let _ = writeln!(output);
+    for (code, key) in EXTRA_KEY_CODES.iter().zip(extras) {
+        write_key(&mut output, code, key);
+    }
+
+    let _ = writeln!(output, "}};");
+
+    output
+}
@@ -0,0 +1,98 @@
+// ZMK .keymap files, holding the layout as the default layer
+//
+// Replace the keymap node in your board's .keymap with this one. The keys
+// around the 30 we lay out come from a standard board for the layout's geometry,
+// with the punctuation the layout displaced moved onto them.
+
+use std::fmt::Write;
+
+use crate::{
+    export::{
+        board::{Board, Slot},
+        fill_extra_keys, fill_keys,
+    },
+    Keyboard,
+};
+
+fn binding(key: u8) -> String {
+    let name = match key {
+        b'a'..=b'z' => return format!("&kp {}", key.to_ascii_uppercase() as char),
+        b'0'..=b'9' => return format!("&kp N{}", key as char),
+        b';' => "SEMI",
+        b':' => "COLON",
+        b'\'' => "SQT",
+        b'"' => "DQT",
+        b',' => "COMMA",
+        b'<' => "LT",
+        b'.' => "DOT",
+        b'>' => "GT",
+        b'/' => "FSLH",
+        b'?' => "QMARK",
+        b'-' => "MINUS",
+        b'_' => "UNDER",
+        b'=' => "EQUAL",
+        b'+' => "PLUS",
+        b'[' => "LBKT",
+        b'{' => "LBRC",
+        b']' => "RBKT",
+        b'}' => "RBRC",
+        b'\\' => "BSLH",
+        b'|' => "PIPE",
+        b'`' => "GRAVE",
+        b'~' => "TILDE",
+        b'!' => "EXCL",
+        _ => return "&none".into(),
+    };
+    format!("&kp {}", name)
+}
+
+pub fn export_zmk(kb: &Keyboard) -> String {
+    let keys = fill_keys(kb, &Keyboard::qwerty());
+    let extras = fill_extra_keys(&keys);
+    let board = Board::for_geometry(&kb.geometry);
+    let mut output = String::new();
+
+    let _ = writeln!(output, "// {}: generated from {}", kb.name, kb);
+    let _ = writeln!(output, "#include <behaviors.dtsi>");
+    let _ = writeln!(output, "#include <dt-bindings/zmk/keys.h>");
+    let _ = writeln!(output);
+    let _ = writeln!(output, "/ {{");
+    let _ = writeln!(output, "    keymap {{");
+    let _ = writeln!(output, "        compatible = \"zmk,keymap\";");
+    let _ = writeln!(output);
+    let _ = writeln!(output, "        default_layer {{");
+    let _ = writeln!(output, "            display-name = \"{}\";", kb.name);
+    let _ = writeln!(output, "            bindings = <");
+
+    for row in &board.rows {
+        let bindings: Vec<_> = row
+            .iter()
+            .map(|slot| match slot {
+                Slot::Key(row, col) => binding(keys[*row][*col]),
+                Slot::Fixed(_, zmk) => zmk.to_string(),
+                Slot::Extra(idx) => extras[*idx].map(binding).unwrap_or_else(|| "&none".into()),
+            })
+            .map(|binding| format!("{:<11}", binding))
+            .collect();
+
+        let _ = writeln!(output, "                {}", bindings.concat().trim_end());
+    }
+
+    let _ = writeln!(output, "            >;");
+    let _ = writeln!(output, "        }};");
+    let _ = writeln!(output, "    }};");
+    let _ = writeln!(output, "}};");
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{tests::check_every_key_once, Format};
+
+    #[test]
+    fn every_key_appears_once() {
+        check_every_key_once(Format::Zmk, binding);
+    }
+}
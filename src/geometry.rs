@@ -0,0 +1,127 @@
+use std::fmt::Display;
+
+// Where the top left corner of each of the 30 keys sits on the board, by row
+// and column, as (x, y) in key widths
+pub type KeyPositions = [[(f32, f32); 10]; 3];
+
+// The physical shape of the board the 30 keys are laid out on
+#[derive(Clone, PartialEq, Debug)]
+pub enum Geometry {
+    // Row staggered, as on most laptop and desktop keyboards
+    Ansi,
+    // Keys in a straight grid
+    Ortho,
+    // Split with staggered columns, as on many ergonomic boards
+    ColumnStagger,
+    // Any other board, such as one imported from keyboard-layout-editor
+    Custom(Box<KeyPositions>),
+}
+
+// How far each column of a column staggered board sits below the highest one
+const COLUMN_STAGGER: [f32; 10] = [0.5, 0.125, 0.0, 0.125, 0.25, 0.25, 0.125, 0.0, 0.125, 0.5];
+
+// The gap between the halves of a split board
+const SPLIT_GAP: f32 = 1.0;
+
+impl Geometry {
+    pub const ALL: [Geometry; 3] = [Geometry::Ansi, Geometry::Ortho, Geometry::ColumnStagger];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Geometry::Ansi => "ansi",
+            Geometry::Ortho => "ortho",
+            Geometry::ColumnStagger => "colstag",
+            Geometry::Custom(_) => "custom",
+        }
+    }
+
+    pub fn key_positions(&self) -> KeyPositions {
+        let mut positions = [[(0.0, 0.0); 10]; 3];
+
+        for (row, keys) in positions.iter_mut().enumerate() {
+            for (col, position) in keys.iter_mut().enumerate() {
+                let (x, y) = (col as f32, row as f32);
+
+                *position = match self {
+                    Geometry::Ansi => (x + [0.0, 0.25, 0.75][row], y),
+                    Geometry::Ortho => (x, y),
+                    Geometry::ColumnStagger => (
+                        if col < 5 { x } else { x + SPLIT_GAP },
+                        y + COLUMN_STAGGER[col],
+                    ),
+                    Geometry::Custom(positions) => positions[row][col],
+                };
+            }
+        }
+
+        positions
+    }
+
+    // The geometry for the given key positions, which is one of the standard
+    // ones if they match it once moved to the top left corner
+    pub fn from_key_positions(positions: KeyPositions) -> Geometry {
+        let min_x = positions
+            .iter()
+            .flatten()
+            .map(|p| p.0)
+            .fold(f32::MAX, f32::min);
+        let min_y = positions
+            .iter()
+            .flatten()
+            .map(|p| p.1)
+            .fold(f32::MAX, f32::min);
+
+        let mut positions = positions;
+        for position in positions.iter_mut().flatten() {
+            *position = (position.0 - min_x, position.1 - min_y);
+        }
+
+        Geometry::ALL
+            .iter()
+            .find(|geometry| {
+                let standard = geometry.key_positions();
+                standard
+                    .iter()
+                    .flatten()
+                    .zip(positions.iter().flatten())
+                    .all(|(a, b)| (a.0 - b.0).abs() < 0.01 && (a.1 - b.1).abs() < 0.01)
+            })
+            .cloned()
+            .unwrap_or(Geometry::Custom(Box::new(positions)))
+    }
+
+    // The standard geometry closest to this one, for exports that only know
+    // about those
+    pub fn standard(&self) -> Geometry {
+        let Geometry::Custom(positions) = self else {
+            return self.clone();
+        };
+
+        // Whether some of the offsets differ from the first
+        fn staggered(offsets: impl Iterator<Item = f32>) -> bool {
+            let offsets: Vec<f32> = offsets.collect();
+            offsets.iter().any(|o| (o - offsets[0]).abs() > 0.01)
+        }
+
+        if staggered(positions[0].iter().map(|p| p.1)) {
+            Geometry::ColumnStagger
+        } else if staggered(positions.iter().map(|keys| keys[0].0)) {
+            Geometry::Ansi
+        } else {
+            Geometry::Ortho
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Geometry> {
+        Geometry::ALL
+            .iter()
+            .find(|geometry| geometry.name() == name)
+            .cloned()
+    }
+}
+
+impl Display for Geometry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
@@ -0,0 +1,121 @@
+// Drawing a layout in the terminal as a grid, split into its two halves, with
+// each key coloured by how often it's pressed
+//
+// Colours are left out when the output isn't a terminal, or the NO_COLOR
+// environment variable is set.
+
+use std::{fmt::Write, io::IsTerminal};
+
+use crate::{Keyboard, Scorer, FINGER_MAP, HEATMAP_WORST_BIGRAMS};
+
+// Background colours from the 256 colour palette, from rarely to often pressed.
+// The first half are dark and get white text, the rest black text.
+const HEAT_COLOURS: [u8; 8] = [236, 24, 30, 35, 142, 178, 208, 196];
+
+// Each key is drawn this many characters wide, not counting its borders
+const CELL_WIDTH: usize = 5;
+
+// The space between the two halves
+const SPLIT: &str = "   ";
+
+fn border(left: &str, middle: &str, right: &str) -> String {
+    let half = vec!["─".repeat(CELL_WIDTH); 5].join(middle);
+    format!(
+        "{}{}{}{}{}{}{}",
+        left, half, right, SPLIT, left, half, right
+    )
+}
+
+fn cell(text: &str, shade: Option<usize>) -> String {
+    match shade {
+        Some(shade) => format!(
+            "\x1b[{};48;5;{}m{:^width$}\x1b[0m",
+            if shade < HEAT_COLOURS.len() / 2 {
+                97
+            } else {
+                30
+            },
+            HEAT_COLOURS[shade],
+            text,
+            width = CELL_WIDTH
+        ),
+        None => format!("{:^width$}", text, width = CELL_WIDTH),
+    }
+}
+
+pub fn render_heatmap(scorer: &Scorer, kb: &Keyboard, overlay: bool) -> String {
+    let colour = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+
+    let frequencies: Vec<Vec<f64>> = kb
+        .rows
+        .iter()
+        .map(|keys| keys.iter().map(|key| scorer.key_frequency(*key)).collect())
+        .collect();
+    let hottest = frequencies.iter().flatten().copied().fold(0.0, f64::max);
+
+    let mut output = String::new();
+
+    for (row, keys) in kb.rows.iter().enumerate() {
+        let _ = match row {
+            0 => writeln!(output, "{}", border("┌", "┬", "┐")),
+            _ => writeln!(output, "{}", border("├", "┼", "┤")),
+        };
+
+        // The key on one line and its frequency on the next
+        for line in 0..2 {
+            for (col, key) in keys.iter().enumerate() {
+                let frequency = frequencies[row][col];
+                let heat = if hottest > 0.0 {
+                    frequency / hottest
+                } else {
+                    0.0
+                };
+                let shade = (heat * (HEAT_COLOURS.len() - 1) as f64).round() as usize;
+
+                let text = match (line, *key) {
+                    (0, key) => (key as char).to_string(),
+                    (_, b'_') => String::new(),
+                    _ => format!("{:.1}", frequency),
+                };
+
+                if col == 5 {
+                    let _ = write!(output, "│{}", SPLIT);
+                }
+                let _ = write!(output, "│{}", cell(&text, colour.then_some(shade)));
+            }
+            let _ = writeln!(output, "│");
+        }
+    }
+    let _ = writeln!(output, "{}", border("└", "┴", "┘"));
+
+    if overlay {
+        // Each finger's load, under the columns it covers
+        let load = scorer.finger_load(kb);
+        let _ = write!(output, " ");
+        for (finger, percentage) in load.fingers.iter().enumerate() {
+            let columns = FINGER_MAP.iter().filter(|f| **f == finger).count();
+            let width = columns * (CELL_WIDTH + 1) - 1;
+
+            if finger == 4 {
+                let _ = write!(output, "{} ", SPLIT);
+            }
+            let _ = write!(
+                output,
+                "{:^width$} ",
+                format!("{:.1}%", percentage),
+                width = width
+            );
+        }
+        let _ = writeln!(output);
+
+        let worst: Vec<String> = scorer
+            .same_finger_bigrams(kb)
+            .iter()
+            .take(HEATMAP_WORST_BIGRAMS)
+            .map(|(bigram, percentage)| format!("{} {:.2}%", bigram, percentage))
+            .collect();
+        let _ = writeln!(output, "worst same finger bigrams: {}", worst.join("  "));
+    }
+
+    output
+}
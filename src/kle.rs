@@ -0,0 +1,316 @@
+// keyboard-layout-editor.com JSON
+//
+// Layouts are exported with the 30 keys at the physical positions of their
+// geometry, so they can be pasted into the editor to share them. Imported files
+// give both the geometry and the keys: in each of the three rows of the file
+// that hold letters, the 10 keys in a row with the most letters are the ones we
+// lay out. The unshifted legend of a key is the one it types, and a key with no
+// legend is a filler. Key rotation is ignored.
+//
+// Files have to be the JSON downloaded from the editor, not the raw data.
+
+use std::fmt::Write;
+
+use serde_json::Value;
+
+use crate::{
+    export::shifted,
+    geometry::{Geometry, KeyPositions},
+    Keyboard,
+};
+
+pub const KLE_EXTENSION: &str = "json";
+
+fn legend(key: u8) -> String {
+    let legend = match key {
+        b'_' => String::new(),
+        b'a'..=b'z' => (key.to_ascii_uppercase() as char).to_string(),
+        _ if shifted(key) != key => format!("{}\n{}", shifted(key) as char, key as char),
+        _ => (key as char).to_string(),
+    };
+
+    Value::String(legend).to_string()
+}
+
+pub fn export_kle(kb: &Keyboard) -> String {
+    let positions = kb.geometry.key_positions();
+
+    let mut output = String::new();
+    let _ = writeln!(output, "[");
+    let _ = writeln!(output, "{},", serde_json::json!({ "name": kb.name }));
+
+    // The editor places each key after the previous one, so only the difference
+    // from there needs writing
+    let (mut x, mut y) = (0.0, 0.0);
+
+    for (row, keys) in kb.rows.iter().enumerate() {
+        let mut items = vec![];
+
+        for (col, key) in keys.iter().enumerate() {
+            let (key_x, key_y) = positions[row][col];
+
+            let mut offset = vec![];
+            if (key_x - x).abs() > 0.001 {
+                offset.push(format!("\"x\":{}", key_x - x));
+            }
+            if (key_y - y).abs() > 0.001 {
+                offset.push(format!("\"y\":{}", key_y - y));
+            }
+            if !offset.is_empty() {
+                items.push(format!("{{{}}}", offset.join(",")));
+            }
+
+            items.push(legend(*key));
+            (x, y) = (key_x + 1.0, key_y);
+        }
+
+        let separator = if row + 1 < kb.rows.len() { "," } else { "" };
+        let _ = writeln!(output, "[{}]{}", items.join(","), separator);
+
+        // Each row of the file starts at the left, one key below the last
+        (x, y) = (0.0, y + 1.0);
+    }
+
+    let _ = writeln!(output, "]");
+
+    output
+}
+
+struct KleKey {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+    legends: Vec<String>,
+}
+
+impl KleKey {
+    // The key typed without shift, or '_' for a key without legends that could
+    // be a filler
+    fn key(&self) -> Option<u8> {
+        let legends: Vec<&str> = self.legends.iter().map(|l| l.trim()).collect();
+
+        if legends.iter().all(|l| l.is_empty()) {
+            return (self.w == 1.0 && self.h == 1.0).then_some(b'_');
+        }
+
+        // A second legend below the first is the unshifted one
+        let legend = match legends.get(1) {
+            Some(l) if !l.is_empty() => l,
+            _ => legends[0],
+        };
+
+        match legend.as_bytes() {
+            [key] if key.is_ascii_graphic() && !key.is_ascii_digit() => {
+                Some(key.to_ascii_lowercase())
+            }
+            _ => None,
+        }
+    }
+}
+
+fn number(value: &Value, property: &str) -> Result<f32, String> {
+    value
+        .as_f64()
+        .map(|n| n as f32)
+        .ok_or_else(|| format!("property '{}' is not a number", property))
+}
+
+// Every key in the file, by the row of the file it's in
+fn parse_keys(source: &str) -> Result<(Option<String>, Vec<Vec<KleKey>>), String> {
+    let json: Value = serde_json::from_str(source).map_err(|e| format!("invalid JSON: {}", e))?;
+    let Value::Array(items) = json else {
+        return Err("expected a JSON array of rows".into());
+    };
+
+    let mut name = None;
+    let mut rows = vec![];
+
+    let (mut x, mut y, mut w, mut h) = (0.0, 0.0, 1.0, 1.0);
+    let (mut rx, mut ry) = (0.0, 0.0);
+
+    for item in items {
+        let row = match item {
+            Value::Object(metadata) if rows.is_empty() => {
+                name = metadata
+                    .get("name")
+                    .and_then(|n| n.as_str())
+                    .map(String::from);
+                continue;
+            }
+            Value::Array(row) => row,
+            _ => return Err(format!("row {} is not an array", rows.len() + 1)),
+        };
+
+        let mut keys = vec![];
+
+        for item in row {
+            match item {
+                Value::String(legend) => {
+                    keys.push(KleKey {
+                        x,
+                        y,
+                        w,
+                        h,
+                        legends: legend.split('\n').map(String::from).collect(),
+                    });
+                    x += w;
+                    (w, h) = (1.0, 1.0);
+                }
+                Value::Object(properties) => {
+                    // A new rotation origin moves back to it
+                    if let Some(value) = properties.get("rx") {
+                        rx = number(value, "rx")?;
+                        (x, y) = (rx, ry);
+                    }
+                    if let Some(value) = properties.get("ry") {
+                        ry = number(value, "ry")?;
+                        (x, y) = (rx, ry);
+                    }
+                    if let Some(value) = properties.get("x") {
+                        x += number(value, "x")?;
+                    }
+                    if let Some(value) = properties.get("y") {
+                        y += number(value, "y")?;
+                    }
+                    if let Some(value) = properties.get("w") {
+                        w = number(value, "w")?;
+                    }
+                    if let Some(value) = properties.get("h") {
+                        h = number(value, "h")?;
+                    }
+                }
+                _ => {
+                    return Err(format!(
+                        "row {} has an item that isn't a key",
+                        rows.len() + 1
+                    ))
+                }
+            }
+        }
+
+        rows.push(keys);
+        (x, y) = (rx, y + 1.0);
+    }
+
+    Ok((name, rows))
+}
+
+pub fn parse_kle(source: &str, default_name: &str) -> Result<Keyboard, String> {
+    let (name, file_rows) = parse_keys(source)?;
+
+    let mut rows = vec![];
+    let mut positions: KeyPositions = [[(0.0, 0.0); 10]; 3];
+
+    for keys in &file_rows {
+        let candidates: Vec<(u8, &KleKey)> = keys
+            .iter()
+            .filter_map(|k| k.key().map(|key| (key, k)))
+            .collect();
+
+        let letters = |keys: &[(u8, &KleKey)]| {
+            keys.iter()
+                .filter(|(key, _)| key.is_ascii_lowercase())
+                .count()
+        };
+
+        if letters(&candidates) == 0 {
+            continue;
+        }
+        if candidates.len() < 10 {
+            return Err(format!(
+                "a row with letters has {} keys we can lay out, expected 10",
+                candidates.len()
+            ));
+        }
+
+        // The first run of 10 with the most letters
+        let start = (0..=candidates.len() - 10)
+            .rev()
+            .max_by_key(|start| letters(&candidates[*start..*start + 10]))
+            .unwrap_or(0);
+        let run = &candidates[start..start + 10];
+
+        if rows.len() < 3 {
+            for (col, (_, k)) in run.iter().enumerate() {
+                positions[rows.len()][col] = (k.x, k.y);
+            }
+        }
+        rows.push(run.iter().map(|(key, _)| *key).collect::<Vec<u8>>());
+    }
+
+    if rows.len() != 3 {
+        return Err(format!(
+            "found {} rows with letters, expected 3",
+            rows.len()
+        ));
+    }
+
+    // Every key other than the filler may only appear once
+    let placed: Vec<u8> = rows.iter().flatten().copied().collect();
+    for (idx, key) in placed.iter().enumerate() {
+        if *key != b'_' && placed[..idx].contains(key) {
+            return Err(format!("duplicate key '{}'", *key as char));
+        }
+    }
+
+    let missing: String = (b'a'..=b'z')
+        .filter(|letter| !placed.contains(letter))
+        .map(|letter| letter as char)
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("missing letters: {}", missing));
+    }
+
+    Ok(Keyboard {
+        name: name.unwrap_or_else(|| default_name.to_string()),
+        rows,
+        geometry: Geometry::from_key_positions(positions),
+        pinned: vec![],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(kb: &Keyboard) {
+        let parsed = parse_kle(&export_kle(kb), "parsed").unwrap();
+
+        assert_eq!(parsed.rows, kb.rows, "{} on {}", kb, kb.geometry);
+        assert_eq!(parsed.geometry, kb.geometry, "{}", kb);
+    }
+
+    #[test]
+    fn round_trips_standard_geometries() {
+        for kb in Keyboard::standard_layouts() {
+            for geometry in Geometry::ALL {
+                round_trip(&Keyboard {
+                    geometry,
+                    ..kb.clone()
+                });
+            }
+        }
+    }
+
+    #[test]
+    fn round_trips_custom_geometry() {
+        // A split ortho board with its right half a little lower
+        let mut positions = Geometry::Ortho.key_positions();
+        for (row, keys) in positions.iter_mut().enumerate() {
+            for (col, position) in keys.iter_mut().enumerate() {
+                if col >= 5 {
+                    *position = (col as f32 + 1.5, row as f32 + 0.25);
+                }
+            }
+        }
+
+        let geometry = Geometry::Custom(Box::new(positions));
+        for kb in Keyboard::standard_layouts() {
+            round_trip(&Keyboard {
+                geometry: geometry.clone(),
+                ..kb
+            });
+        }
+    }
+}
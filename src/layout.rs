@@ -0,0 +1,330 @@
+// Layout definition files
+//
+// A layout file describes one layout in plain text:
+//
+//     # Lines starting with '#' and a space are comments
+//     name: colemak-dh
+//     geometry: ansi
+//     qwfpb jluy;
+//     arstg mneio
+//     zxcdv kh,./
+//     pinned: ,./
+//
+// The three rows hold 10 keys each, which have to be ASCII. Spaces inside a row
+// are ignored, so the two halves can be written apart, and a row can start with
+// '#' as long as it's not followed by a space. '_' marks a key that holds
+// nothing we score. The geometry defaults to ansi, and pinned keys stay in place
+// while optimising, leaving at least two other keys to swap.
+// Layout files use the .layout extension. Files exported from
+// keyboard-layout-editor.com can be used as well, see kle.rs.
+
+use std::{fmt::Display, path::Path};
+
+use crate::{
+    geometry::Geometry,
+    kle::{parse_kle, KLE_EXTENSION},
+    Keyboard,
+};
+
+pub const LAYOUT_EXTENSION: &str = "layout";
+
+#[derive(Debug)]
+pub struct LayoutError {
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl LayoutError {
+    fn new(line: Option<usize>, message: String) -> LayoutError {
+        LayoutError { line, message }
+    }
+}
+
+impl Display for LayoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+pub fn parse_layout(source: &str) -> Result<Keyboard, LayoutError> {
+    let mut name = None;
+    let mut geometry = Geometry::Ansi;
+    let mut rows: Vec<(usize, Vec<u8>)> = vec![];
+    let mut pinned = vec![];
+
+    for (idx, line) in source.lines().enumerate() {
+        let line_number = Some(idx + 1);
+        let line = line.trim();
+
+        let comment = line
+            .strip_prefix('#')
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace));
+        if line.is_empty() || comment {
+            continue;
+        }
+
+        if let Some(value) = line.strip_prefix("name:") {
+            let value = value.trim();
+            if value.is_empty() {
+                return Err(LayoutError::new(line_number, "name is empty".into()));
+            }
+            name = Some(value.to_string());
+        } else if let Some(value) = line.strip_prefix("geometry:") {
+            let value = value.trim();
+            geometry = Geometry::from_name(value).ok_or_else(|| {
+                let known: Vec<_> = Geometry::ALL.iter().map(|g| g.name()).collect();
+                LayoutError::new(
+                    line_number,
+                    format!(
+                        "unknown geometry '{}', expected one of: {}",
+                        value,
+                        known.join(", ")
+                    ),
+                )
+            })?;
+        } else if !line.is_ascii() {
+            return Err(LayoutError::new(
+                line_number,
+                "keys have to be ASCII characters".into(),
+            ));
+        } else if let Some(value) = line.strip_prefix("pinned:") {
+            pinned.extend(value.bytes().filter(|b| !b.is_ascii_whitespace()));
+        } else {
+            let keys: Vec<u8> = line.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+
+            if keys.len() != 10 {
+                return Err(LayoutError::new(
+                    line_number,
+                    format!("row has {} keys, expected 10", keys.len()),
+                ));
+            }
+            rows.push((idx + 1, keys));
+        }
+    }
+
+    let name = name.ok_or_else(|| LayoutError::new(None, "missing 'name:' line".into()))?;
+
+    if rows.len() != 3 {
+        return Err(LayoutError::new(
+            rows.get(3).map(|(line, _)| *line),
+            format!("found {} rows, expected 3", rows.len()),
+        ));
+    }
+
+    // Every key other than the filler may only appear once
+    let mut seen: Vec<(u8, usize)> = vec![];
+    for (line, keys) in &rows {
+        for key in keys {
+            if *key == b'_' {
+                continue;
+            }
+            if let Some((_, first_line)) = seen.iter().find(|(k, _)| k == key) {
+                return Err(LayoutError::new(
+                    Some(*line),
+                    format!(
+                        "duplicate key '{}', already on line {}",
+                        *key as char, first_line
+                    ),
+                ));
+            }
+            seen.push((*key, *line));
+        }
+    }
+
+    let missing: String = (b'a'..=b'z')
+        .filter(|letter| !seen.iter().any(|(k, _)| k == letter))
+        .map(|letter| letter as char)
+        .collect();
+    if !missing.is_empty() {
+        return Err(LayoutError::new(
+            None,
+            format!("missing letters: {}", missing),
+        ));
+    }
+
+    for key in &pinned {
+        if !seen.iter().any(|(k, _)| k == key) {
+            return Err(LayoutError::new(
+                None,
+                format!("pinned key '{}' is not on the layout", *key as char),
+            ));
+        }
+    }
+
+    // The optimisers need two keys to swap
+    let free = seen.iter().filter(|(k, _)| !pinned.contains(k)).count();
+    if free < 2 {
+        return Err(LayoutError::new(
+            None,
+            format!("at least 2 keys have to be left unpinned, found {}", free),
+        ));
+    }
+
+    Ok(Keyboard {
+        name,
+        rows: rows.into_iter().map(|(_, keys)| keys).collect(),
+        geometry,
+        pinned,
+    })
+}
+
+pub fn load_layout(path: &Path) -> Result<Keyboard, String> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|e| format!("{}: couldn't read layout: {}", path.display(), e))?;
+
+    if path.extension().is_some_and(|ext| ext == KLE_EXTENSION) {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        return parse_kle(&source, &name).map_err(|e| format!("{}: {}", path.display(), e));
+    }
+
+    parse_layout(&source).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// Every layout file and keyboard-layout-editor file in the directory, in the order of their file names
+pub fn load_layout_dir(dir: &Path) -> Result<Vec<Keyboard>, String> {
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| format!("{}: couldn't read layout directory: {}", dir.display(), e))?;
+
+    let mut paths = vec![];
+    for entry in entries {
+        let path = entry
+            .map_err(|e| format!("{}: couldn't read layout directory: {}", dir.display(), e))?
+            .path();
+
+        if path
+            .extension()
+            .is_some_and(|ext| ext == LAYOUT_EXTENSION || ext == KLE_EXTENSION)
+        {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    paths.iter().map(|path| load_layout(path)).collect()
+}
+
+// A layout given on the command line, either as the path of a layout file or as
+// the 30 keys printed by the optimiser, or a whole line of output.log with the
+// version and score in front and the seed behind
+pub fn parse_layout_arg(arg: &str) -> Result<Keyboard, String> {
+    let path = Path::new(arg);
+    if path.exists() {
+        return load_layout(path);
+    }
+
+    let fields: Vec<&str> = arg.split('|').collect();
+    let keys = match fields.len() {
+        3 | 4 => fields[2],
+        _ => arg,
+    }
+    .trim();
+    let keys: Vec<char> = keys.chars().collect();
+    if keys.len() != 30 {
+        return Err(format!(
+            "'{}' is neither a layout file nor a layout of 30 keys",
+            arg
+        ));
+    }
+
+    let row = |row: usize| keys[row * 10..row * 10 + 10].iter().collect::<String>();
+    let keys: String = keys.iter().collect();
+    let source = format!("name: custom\n{}\n{}\n{}\n", row(0), row(1), row(2));
+    parse_layout(&source).map_err(|e| format!("'{}': {}", keys, e.message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(rows: &str, extra: &str) -> String {
+        format!("name: test\n{}\n{}", rows, extra)
+    }
+
+    const QWERTY: &str = "qwertyuiop\nasdfghjkl;\nzxcvbnm,./";
+
+    fn error(source: &str) -> LayoutError {
+        match parse_layout(source) {
+            Ok(kb) => panic!("parsed as {}", kb),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn parses_a_layout() {
+        let source = layout(
+            "# a comment\nqwert yuiop\nasdfg hjkl;\nzxcvb nm,./",
+            "pinned: ,./",
+        );
+        let kb = parse_layout(&source).unwrap();
+
+        assert_eq!(kb.to_string(), "qwertyuiopasdfghjkl;zxcvbnm,./");
+        assert_eq!(kb.pinned, b",./");
+        assert_eq!(kb.geometry, Geometry::Ansi);
+    }
+
+    #[test]
+    fn row_can_start_with_hash() {
+        let kb = parse_layout(&layout("#wertyuiop\nasdfghjkl;\nzxcvbnmq./", "")).unwrap();
+        assert_eq!(kb.rows[0][0], b'#');
+    }
+
+    #[test]
+    fn rejects_duplicate_key() {
+        let e = error(&layout("qwertyuiop\nasdfghjkl;\nzxcvbnm,.q", ""));
+        assert_eq!(e.line, Some(4));
+        assert!(e.message.contains("duplicate key 'q'"), "{}", e);
+    }
+
+    #[test]
+    fn rejects_missing_letters() {
+        let e = error(&layout("qwertyuiop\nasdfghjkl;\n_xcvbnm,./", ""));
+        assert_eq!(e.message, "missing letters: z");
+    }
+
+    #[test]
+    fn rejects_wrong_row_length() {
+        let e = error(&layout("qwertyuiop\nasdfghjkl\nzxcvbnm,./", ""));
+        assert_eq!(e.line, Some(3));
+        assert_eq!(e.message, "row has 9 keys, expected 10");
+    }
+
+    #[test]
+    fn rejects_wrong_row_count() {
+        let e = error(&layout("qwertyuiop\nasdfghjkl;", ""));
+        assert_eq!(e.message, "found 2 rows, expected 3");
+    }
+
+    #[test]
+    fn rejects_unknown_geometry() {
+        let e = error(&layout(QWERTY, "geometry: round"));
+        assert_eq!(e.line, Some(5));
+        assert!(e.message.starts_with("unknown geometry 'round'"), "{}", e);
+    }
+
+    #[test]
+    fn rejects_pinned_key_not_on_layout() {
+        let e = error(&layout(QWERTY, "pinned: '"));
+        assert_eq!(e.message, "pinned key ''' is not on the layout");
+    }
+
+    #[test]
+    fn rejects_non_ascii_keys() {
+        let e = error(&layout("qwertyuiop\nasdfghjklé\nzxcvbnm,./", ""));
+        assert_eq!(e.line, Some(3));
+
+        let e = error(&layout(QWERTY, "pinned: é"));
+        assert_eq!(e.line, Some(5));
+    }
+
+    #[test]
+    fn rejects_pinning_all_but_one_key() {
+        let e = error(&layout(QWERTY, "pinned: qwertyuiopasdfghjkl;zxcvbnm,."));
+        assert_eq!(
+            e.message,
+            "at least 2 keys have to be left unpinned, found 1"
+        );
+    }
+}
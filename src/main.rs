@@ -1,18 +1,94 @@
+mod checkpoint;
+mod export;
+mod geometry;
+mod heatmap;
+mod kle;
+mod layout;
+mod optimise;
+mod options;
+mod svg;
+
 use std::{
+    cell::Cell,
     collections::HashMap,
     fmt::Display,
     fs::OpenOptions,
     io::{Read, Write},
-    sync::mpsc::channel,
-    thread,
+    path::Path,
+    sync::{mpsc::RecvTimeoutError, Arc},
+    time::{Duration, Instant},
 };
 
+use checkpoint::Checkpoint;
+use geometry::Geometry;
 use iter_tools::prelude::*;
-use rand::{seq::SliceRandom, thread_rng, RngCore};
+use optimise::{Found, Progress};
+use rand::{seq::SliceRandom, Rng};
 
-const ALGORITHM_VERSION: f64 = 4.419;
+const ALGORITHM_VERSION: f64 = 4.43;
 const MAX_SAMPLES_PER_CATEGORY: usize = 1000;
 const FAILED_TO_IMPROVE_LIMIT: usize = 1000;
+// The default for --threads
+const WORKER_THREADS: usize = 9;
+// How often to check the time and evaluation budgets while waiting for results
+const BUDGET_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+// How many of the best layouts to show when the search stops, and to keep in
+// checkpoints
+const SUMMARY_LAYOUTS: usize = 5;
+// How often to save the search with --checkpoint
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(60);
+
+// Simulated annealing. Temperatures are relative to the typical cost of a swap
+// that makes a shuffled layout worse, so at a temperature of 1.0 such a swap is
+// taken about a third of the time.
+const ANNEAL_INITIAL_TEMPERATURE: f64 = 1.0;
+const ANNEAL_FINAL_TEMPERATURE: f64 = 0.001;
+// After each step the temperature is multiplied by ANNEAL_COOLING_FACTOR, or
+// with linear cooling lowered by ANNEAL_COOLING_STEP of the initial temperature
+const USE_LINEAR_COOLING: bool = false;
+const ANNEAL_COOLING_FACTOR: f64 = 0.95;
+const ANNEAL_COOLING_STEP: f64 = 0.01;
+// How many swaps are tried at each temperature before cooling
+const ANNEAL_STEPS_PER_TEMPERATURE: usize = 1000;
+// After this many temperatures without a new best, heat back up to the initial
+// temperature scaled by ANNEAL_REHEAT_FACTOR, at most ANNEAL_MAX_REHEATS times
+const ANNEAL_REHEAT_AFTER: usize = 20;
+const ANNEAL_REHEAT_FACTOR: f64 = 0.5;
+const ANNEAL_MAX_REHEATS: usize = 3;
+
+// Genetic algorithm. Each generation keeps the best GENETIC_ELITES layouts and
+// breeds the rest from parents that won a tournament of GENETIC_TOURNAMENT_SIZE.
+// A child gets another random swap while a roll stays under the mutation rate.
+const GENETIC_POPULATION: usize = 100;
+const GENETIC_ELITES: usize = 2;
+const GENETIC_TOURNAMENT_SIZE: usize = 3;
+const GENETIC_MUTATION_RATE: f64 = 0.3;
+// Breed children with partially mapped crossover rather than order crossover
+const USE_PMX_CROSSOVER: bool = false;
+// Stop after this many generations without a better layout
+const GENETIC_STALL_LIMIT: usize = 50;
+
+// Tabu search. A pair of keys can't be swapped again until this many other swaps
+// have been made, and a search stops after TABU_STALL_LIMIT swaps without a
+// better layout.
+const TABU_TENURE: usize = 20;
+const TABU_STALL_LIMIT: usize = 200;
+
+// Parallel tempering. Each worker thread runs a chain at its own temperature,
+// spread evenly on a log scale between these two (relative to the typical cost
+// of a bad swap, as for annealing). Every TEMPERING_EXCHANGE_INTERVAL swaps,
+// neighbouring chains offer to trade layouts.
+const TEMPERING_MIN_TEMPERATURE: f64 = 0.01;
+const TEMPERING_MAX_TEMPERATURE: f64 = 1.0;
+const TEMPERING_EXCHANGE_INTERVAL: usize = 10000;
+
+// Island model. Every ISLAND_MIGRATION_INTERVAL swaps, each island shares its
+// best layout with a pool of the ISLAND_POOL_SIZE best layouts found, and takes
+// one from there if it beats its own. A stuck island is kicked with
+// ISLAND_KICK_SWAPS random swaps.
+const ISLAND_MIGRATION_INTERVAL: usize = 20000;
+const ISLAND_POOL_SIZE: usize = 5;
+const ISLAND_KICK_SWAPS: usize = 3;
 
 const SINGLE_TOP: i64 = 0;
 const SINGLE_MIDDLE: i64 = 3;
@@ -46,8 +122,43 @@ const MINOR_FINGER_CURL_PENALTY: i64 = -5;
 const PINKIE_PENALTY: i64 = -5;
 const TWO_ROW_MOVE_PENALTY: i64 = -10;
 const SAME_FINGER_PENALTY: i64 = -5;
+const LATERAL_STRETCH_PENALTY: i64 = -10;
+const FULL_SCISSOR_PENALTY: i64 = -15;
+const HALF_SCISSOR_PENALTY: i64 = -5;
+
+// Applied per key press away from the target load of each finger and hand
+const FINGER_LOAD_PENALTY: i64 = -1;
+const HAND_LOAD_PENALTY: i64 = -2;
+
+const TRIGRAM_ALTERNATE: i64 = 0;
+const TRIGRAM_ROLL_IN: i64 = 0;
+const TRIGRAM_ROLL_OUT: i64 = 0;
+const TRIGRAM_ONE_HAND: i64 = 0;
+const TRIGRAM_REDIRECT: i64 = 0;
+const TRIGRAM_BAD_REDIRECT: i64 = 0;
+
+// How many n-grams to show for the top bonuses and penalties in a score breakdown
+const BREAKDOWN_TOP_NGRAMS: usize = 10;
+// How many of the worst same finger bigrams the heatmap overlay lists
+const HEATMAP_WORST_BIGRAMS: usize = 5;
+// How many of the most frequent same finger bigrams and rolls the SVG draws arcs for
+const SVG_TOP_BIGRAMS: usize = 8;
 
 const USE_QUADRUPLE_ROLL: bool = false;
+// Use the scissor penalties instead of the TWO_ROW_MOVE_PENALTY
+const USE_SCISSORS: bool = false;
+
+// Finish every search with steepest ascent, so each result is a layout that no
+// single swap can improve
+const USE_POLISH: bool = true;
+
+// Which finger presses each column, from the left pinkie (0) to the right pinkie (7).
+// The index fingers also cover the two center columns.
+const FINGER_MAP: [usize; 10] = [0, 1, 2, 3, 3, 4, 4, 5, 6, 7];
+// How far each finger reaches, from shortest (0) to longest (3)
+const FINGER_LENGTH: [usize; 8] = [0, 2, 3, 1, 1, 3, 2, 0];
+// Percentage of key presses each finger should ideally take
+const FINGER_TARGET_LOAD: [f64; 8] = [8.0, 11.0, 15.0, 16.0, 16.0, 15.0, 11.0, 8.0];
 
 // This will prevent using the ,./ keys
 const BOTTOM_RIGHT_PENALTY: i64 = -10000000;
@@ -57,6 +168,9 @@ const BOTTOM_RIGHT_PENALTY: i64 = -10000000;
 struct Keyboard {
     name: String,
     rows: Vec<Vec<u8>>,
+    geometry: Geometry,
+    // Keys that stay where they are while optimising
+    pinned: Vec<u8>,
 }
 
 impl Display for Keyboard {
@@ -79,6 +193,8 @@ impl Keyboard {
                 "asdfghjkl;".into(),
                 "zxcvbnm,./".into(),
             ],
+            geometry: Geometry::Ansi,
+            pinned: vec![],
         }
     }
 
@@ -90,6 +206,8 @@ impl Keyboard {
                 "aoeuidhtns".into(),
                 ";qjkxbmwvz".into(),
             ],
+            geometry: Geometry::Ansi,
+            pinned: vec![],
         }
     }
 
@@ -101,6 +219,8 @@ impl Keyboard {
                 "arstdhneio".into(),
                 "zxcvbkm,./".into(),
             ],
+            geometry: Geometry::Ansi,
+            pinned: vec![],
         }
     }
 
@@ -112,6 +232,151 @@ impl Keyboard {
                 "ashtgyneoi".into(),
                 "zxmcvkl,./".into(),
             ],
+            geometry: Geometry::Ansi,
+            pinned: vec![],
+        }
+    }
+
+    pub fn colemak_dh() -> Keyboard {
+        Keyboard {
+            name: "colemak-dh".into(),
+            rows: vec![
+                "qwfpbjluy;".into(),
+                "arstgmneio".into(),
+                "zxcdvkh,./".into(),
+            ],
+            geometry: Geometry::ColumnStagger,
+            pinned: vec![],
+        }
+    }
+
+    pub fn halmak() -> Keyboard {
+        Keyboard {
+            name: "halmak".into(),
+            rows: vec![
+                "wlrbz;qudj".into(),
+                "shnt,.aeoi".into(),
+                "fmvc/gpxky".into(),
+            ],
+            geometry: Geometry::Ansi,
+            pinned: vec![],
+        }
+    }
+
+    pub fn norman() -> Keyboard {
+        Keyboard {
+            name: "norman".into(),
+            rows: vec![
+                "qwdfkjurl;".into(),
+                "asetgynioh".into(),
+                "zxcvbpm,./".into(),
+            ],
+            geometry: Geometry::Ansi,
+            pinned: vec![],
+        }
+    }
+
+    pub fn mtgap() -> Keyboard {
+        Keyboard {
+            name: "mtgap".into(),
+            rows: vec![
+                "ypoujkdlcw".into(),
+                "inea,mhtsr".into(),
+                "qz/.;bfgvx".into(),
+            ],
+            geometry: Geometry::Ansi,
+            pinned: vec![],
+        }
+    }
+
+    pub fn beakl() -> Keyboard {
+        Keyboard {
+            name: "beakl".into(),
+            rows: vec![
+                "qhouxgcrfz".into(),
+                "yiea.dstnb".into(),
+                "j/,k'wmlpv".into(),
+            ],
+            geometry: Geometry::Ansi,
+            pinned: vec![],
+        }
+    }
+
+    pub fn isrt() -> Keyboard {
+        Keyboard {
+            name: "isrt".into(),
+            rows: vec![
+                "yclmkzfu,'".into(),
+                "isrtgpneao".into(),
+                "qvwdjbh/.x".into(),
+            ],
+            geometry: Geometry::Ansi,
+            pinned: vec![],
+        }
+    }
+
+    pub fn canary() -> Keyboard {
+        Keyboard {
+            name: "canary".into(),
+            rows: vec![
+                "wlypkzxou;".into(),
+                "crstbfneia".into(),
+                "jvdgqmh/,.".into(),
+            ],
+            geometry: Geometry::ColumnStagger,
+            pinned: vec![],
+        }
+    }
+
+    pub fn graphite() -> Keyboard {
+        Keyboard {
+            name: "graphite".into(),
+            rows: vec![
+                "bldwz'fouj".into(),
+                "nrtsgyhaei".into(),
+                "qxmcvkp.-/".into(),
+            ],
+            geometry: Geometry::Ansi,
+            pinned: vec![],
+        }
+    }
+
+    pub fn sturdy() -> Keyboard {
+        Keyboard {
+            name: "sturdy".into(),
+            rows: vec![
+                "vmlcpxfouj".into(),
+                "strdy.naei".into(),
+                "zkqgwbh';,".into(),
+            ],
+            geometry: Geometry::Ansi,
+            pinned: vec![],
+        }
+    }
+
+    pub fn qwertz() -> Keyboard {
+        Keyboard {
+            name: "qwertz".into(),
+            rows: vec![
+                "qwertzuiop".into(),
+                "asdfghjkl;".into(),
+                "yxcvbnm,.-".into(),
+            ],
+            geometry: Geometry::Ansi,
+            pinned: vec![],
+        }
+    }
+
+    pub fn azerty() -> Keyboard {
+        Keyboard {
+            name: "azerty".into(),
+            rows: vec![
+                "azertyuiop".into(),
+                "qsdfghjklm".into(),
+                "wxcvbn,;:!".into(),
+            ],
+            geometry: Geometry::Ansi,
+            pinned: vec![],
         }
     }
 
@@ -126,26 +391,201 @@ impl Keyboard {
     //     }
     // }
 
-    pub fn random_layout() -> Keyboard {
+    // Every built-in layout, for comparing against
+    pub fn standard_layouts() -> Vec<Keyboard> {
+        vec![
+            Keyboard::qwerty(),
+            Keyboard::dvorak(),
+            Keyboard::colemak(),
+            Keyboard::workman(),
+            Keyboard::colemak_dh(),
+            Keyboard::halmak(),
+            Keyboard::norman(),
+            Keyboard::mtgap(),
+            Keyboard::beakl(),
+            Keyboard::isrt(),
+            Keyboard::canary(),
+            Keyboard::graphite(),
+            Keyboard::sturdy(),
+            Keyboard::qwertz(),
+            Keyboard::azerty(),
+        ]
+    }
+
+    pub fn random_layout(rng: &mut impl Rng) -> Keyboard {
         let mut keys: Vec<u8> = "abcdefghijklmnopqrstuvwxyz____".into();
 
-        keys.shuffle(&mut thread_rng());
+        keys.shuffle(rng);
 
         Keyboard {
             name: "random".into(),
             rows: vec![keys[0..10].into(), keys[10..20].into(), keys[20..30].into()],
+            geometry: Geometry::Ansi,
+            pinned: vec![],
         }
     }
 
-    pub fn find_key(&self, key: u8) -> (usize, usize) {
-        for row in 0..3 {
-            for col in 0..10 {
-                if self.rows[row][col] == key {
-                    return (row, col);
-                }
+    // The same layout with every key that isn't pinned moved to a random position
+    pub fn shuffled(&self, rng: &mut impl Rng) -> Keyboard {
+        let mut kb = self.clone();
+        let free: Vec<_> = (0..3)
+            .cartesian_product(0..10)
+            .filter(|(row, col)| !self.pinned.contains(&self.rows[*row][*col]))
+            .collect();
+
+        let mut keys: Vec<u8> = free
+            .iter()
+            .map(|(row, col)| self.rows[*row][*col])
+            .collect();
+        keys.shuffle(rng);
+
+        for ((row, col), key) in free.into_iter().zip(keys) {
+            kb.rows[row][col] = key;
+        }
+        kb
+    }
+
+    pub fn swap(&mut self, from: (usize, usize), to: (usize, usize)) {
+        let prev = self.rows[to.0][to.1];
+        self.rows[to.0][to.1] = self.rows[from.0][from.1];
+        self.rows[from.0][from.1] = prev;
+    }
+
+    pub fn positions(&self) -> Positions {
+        let mut positions = [NO_POSITION; 256];
+
+        for (row, keys) in self.rows.iter().enumerate() {
+            for (col, key) in keys.iter().enumerate() {
+                positions[*key as usize] = (row * 10 + col) as u8;
+            }
+        }
+        positions
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TrigramClass {
+    Alternate,
+    RollIn,
+    RollOut,
+    OneHand,
+    Redirect,
+    BadRedirect,
+    Other,
+}
+
+impl TrigramClass {
+    const ALL: [TrigramClass; 7] = [
+        TrigramClass::Alternate,
+        TrigramClass::RollIn,
+        TrigramClass::RollOut,
+        TrigramClass::OneHand,
+        TrigramClass::Redirect,
+        TrigramClass::BadRedirect,
+        TrigramClass::Other,
+    ];
+
+    pub fn classify(cols: (usize, usize, usize)) -> TrigramClass {
+        let (a, b, c) = cols;
+
+        if !same_hand(a, b) && !same_hand(b, c) {
+            return TrigramClass::Alternate;
+        }
+
+        if same_hand(a, b) && same_hand(b, c) {
+            let (a, b, c) = (finger_rank(a), finger_rank(b), finger_rank(c));
+
+            if a == b || b == c || a == c {
+                return TrigramClass::Other;
+            }
+            if (a < b) == (b < c) {
+                return TrigramClass::OneHand;
+            }
+            // Index fingers have the highest rank
+            if a == 3 || b == 3 || c == 3 {
+                return TrigramClass::Redirect;
+            }
+            return TrigramClass::BadRedirect;
+        }
+
+        // Two keys on one hand, one on the other
+        let (from, to) = if same_hand(a, b) { (a, b) } else { (b, c) };
+        let (from, to) = (finger_rank(from), finger_rank(to));
+
+        if from < to {
+            TrigramClass::RollIn
+        } else if from > to {
+            TrigramClass::RollOut
+        } else {
+            TrigramClass::Other
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TrigramClass::Alternate => "alternate",
+            TrigramClass::RollIn => "roll-in",
+            TrigramClass::RollOut => "roll-out",
+            TrigramClass::OneHand => "one-hand",
+            TrigramClass::Redirect => "redirect",
+            TrigramClass::BadRedirect => "bad-redirect",
+            TrigramClass::Other => "other",
+        }
+    }
+
+    pub fn weight(&self) -> i64 {
+        match self {
+            TrigramClass::Alternate => TRIGRAM_ALTERNATE,
+            TrigramClass::RollIn => TRIGRAM_ROLL_IN,
+            TrigramClass::RollOut => TRIGRAM_ROLL_OUT,
+            TrigramClass::OneHand => TRIGRAM_ONE_HAND,
+            TrigramClass::Redirect => TRIGRAM_REDIRECT,
+            TrigramClass::BadRedirect => TRIGRAM_BAD_REDIRECT,
+            TrigramClass::Other => 0,
+        }
+    }
+}
+
+// Percentage of the trigrams in the corpus that fall into each class
+struct TrigramStats {
+    percentages: Vec<(TrigramClass, f64)>,
+}
+
+impl Display for TrigramStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (idx, (class, percentage)) in self.percentages.iter().enumerate() {
+            if idx > 0 {
+                write!(f, "  ")?;
             }
+            write!(f, "{}: {:.2}%", class.name(), percentage)?;
         }
-        panic!("Key not on keyboard")
+        Ok(())
+    }
+}
+
+// Percentage of key presses taken by each finger and each hand
+struct FingerLoad {
+    fingers: [f64; 8],
+    hands: [f64; 2],
+}
+
+impl Display for FingerLoad {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "left: {:.2}% (", self.hands[0])?;
+        for (idx, finger) in self.fingers[0..4].iter().enumerate() {
+            if idx > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{:.2}", finger)?;
+        }
+        write!(f, ")  right: {:.2}% (", self.hands[1])?;
+        for (idx, finger) in self.fingers[4..].iter().enumerate() {
+            if idx > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{:.2}", finger)?;
+        }
+        write!(f, ")")
     }
 }
 
@@ -155,356 +595,877 @@ struct Scorer {
     double_byte: Vec<(Vec<u8>, i64)>,
     triple_byte: Vec<(Vec<u8>, i64)>,
     quadruple_byte: Vec<(Vec<u8>, i64)>,
+
+    // Precomputed tables for each n-gram size, from singles to quadruples. They
+    // never change once built, so the workers' clones all share them.
+    tables: Arc<Vec<NgramTable>>,
+    single_total: i64,
 }
 
-impl Scorer {
-    fn score_singles(&self, kb: &Keyboard) -> i64 {
-        let mut total = 0;
+thread_local! {
+    // How many layouts and swaps this thread has scored since it last reported
+    // them, for the evaluation budget
+    static EVALUATIONS: Cell<u64> = const { Cell::new(0) };
+}
 
-        for byte in &self.single_byte {
-            if kb.rows[0].contains(&byte.0) {
-                total += byte.1 * SINGLE_TOP;
-            } else if kb.rows[1].contains(&byte.0) {
-                total += byte.1 * SINGLE_MIDDLE;
-            } else if kb.rows[2].contains(&byte.0) {
-                total += byte.1 * SINGLE_BOTTOM;
-            }
-        }
-        total
-    }
+fn count_evaluation() {
+    EVALUATIONS.with(|evaluations| evaluations.set(evaluations.get() + 1));
+}
 
-    fn score_doubles(&self, kb: &Keyboard) -> i64 {
-        let mut total = 0;
+fn take_evaluations() -> u64 {
+    EVALUATIONS.with(|evaluations| evaluations.take())
+}
 
-        'top: for bytes in &self.double_byte {
-            let forward = (bytes.0[0], bytes.0[1]);
-            let backward = (bytes.0[1], bytes.0[0]);
+// Where each key sits on a layout, as an index into the 30 positions (row * 10 + col)
+type Positions = [u8; 256];
 
-            // Left hand, middle row
-            for (a, b) in kb.rows[1][0..4].iter().tuple_windows() {
-                let value = (*a, *b);
+const NUM_POSITIONS: usize = 30;
+const NO_POSITION: u8 = u8::MAX;
 
-                if value == forward {
-                    total += bytes.1 * DOUBLE_MIDDLE_INWARD;
-                    continue 'top;
-                }
-                if value == backward {
-                    total += bytes.1 * DOUBLE_MIDDLE_OUTWARD;
-                    continue 'top;
-                }
-            }
+fn position_coords(position: u8) -> (usize, usize) {
+    debug_assert!(
+        (position as usize) < NUM_POSITIONS,
+        "a key isn't on the layout"
+    );
+    (position as usize / 10, position as usize % 10)
+}
 
-            // Right hand, middle row
-            for (a, b) in kb.rows[1][6..].iter().tuple_windows() {
-                let value = (*a, *b);
+// The n-grams of one size, along with the combined weight of every enabled metric
+// for each possible placement of their keys
+#[derive(Clone)]
+struct NgramTable {
+    size: usize,
+    keys: Vec<u8>,
+    counts: Vec<i64>,
+    weights: Vec<i64>,
+    flat_weights: Vec<i64>,
+
+    // For each key, the n-grams that key appears in
+    by_key: Vec<Vec<u32>>,
+}
 
-                if value == backward {
-                    total += bytes.1 * DOUBLE_MIDDLE_INWARD;
-                    continue 'top;
-                }
-                if value == forward {
-                    total += bytes.1 * DOUBLE_MIDDLE_OUTWARD;
-                    continue 'top;
-                }
+impl NgramTable {
+    fn new(size: usize, ngrams: Vec<(&[u8], i64)>) -> NgramTable {
+        let metrics: Vec<_> = METRICS
+            .iter()
+            .filter(|metric| metric.enabled && metric.size == size)
+            .collect();
+
+        let mut table = NgramTable {
+            size,
+            keys: vec![],
+            counts: vec![],
+            weights: vec![],
+            flat_weights: vec![],
+            by_key: vec![vec![]; 256],
+        };
+
+        // Nothing scores n-grams of this size, so skip them entirely
+        if metrics.is_empty() {
+            return table;
+        }
+
+        let num_placements = NUM_POSITIONS.pow(size as u32);
+        table.weights = vec![0; num_placements];
+        if metrics.iter().any(|metric| !metric.scaled) {
+            table.flat_weights = vec![0; num_placements];
+        }
+
+        let mut coords = vec![(0, 0); size];
+        for placement in 0..num_placements {
+            let mut rest = placement;
+            for coord in coords.iter_mut().rev() {
+                *coord = position_coords((rest % NUM_POSITIONS) as u8);
+                rest /= NUM_POSITIONS;
             }
 
-            // Left hand, top row
-            for (a, b) in kb.rows[0][0..4].iter().tuple_windows() {
-                let value = (*a, *b);
+            for metric in &metrics {
+                let weight = (metric.score)(&coords);
 
-                if value == forward {
-                    total += bytes.1 * DOUBLE_TOP_INWARD;
-                    continue 'top;
-                }
-                if value == backward {
-                    total += bytes.1 * DOUBLE_TOP_OUTWARD;
-                    continue 'top;
+                if metric.scaled {
+                    table.weights[placement] += weight;
+                } else {
+                    table.flat_weights[placement] += weight;
                 }
             }
+        }
 
-            // Right hand, top row
-            for (a, b) in kb.rows[0][6..].iter().tuple_windows() {
-                let value = (*a, *b);
+        for (idx, (bytes, count)) in ngrams.into_iter().enumerate() {
+            table.keys.extend_from_slice(bytes);
+            table.counts.push(count);
 
-                if value == backward {
-                    total += bytes.1 * DOUBLE_MIDDLE_INWARD;
-                    continue 'top;
-                }
-                if value == forward {
-                    total += bytes.1 * DOUBLE_MIDDLE_OUTWARD;
-                    continue 'top;
-                }
+            for byte in bytes.iter().unique() {
+                table.by_key[*byte as usize].push(idx as u32);
             }
         }
-        total
+
+        table
     }
 
-    fn score_triples(&self, kb: &Keyboard) -> i64 {
-        let mut total = 0;
+    fn ngram(&self, idx: usize) -> &[u8] {
+        &self.keys[idx * self.size..(idx + 1) * self.size]
+    }
+
+    fn score(&self, positions: &Positions, idx: usize) -> i64 {
+        let mut placement = 0;
+        for key in self.ngram(idx) {
+            let position = positions[*key as usize] as usize;
+            assert!(
+                position < NUM_POSITIONS,
+                "'{}' isn't on the layout",
+                *key as char
+            );
+            placement = placement * NUM_POSITIONS + position;
+        }
 
-        'top: for bytes in &self.triple_byte {
-            let forward = (bytes.0[0], bytes.0[1], bytes.0[2]);
-            let backward = (bytes.0[2], bytes.0[1], bytes.0[0]);
+        let mut score = self.counts[idx] * self.weights[placement];
+        if !self.flat_weights.is_empty() {
+            score += self.flat_weights[placement];
+        }
+        score
+    }
 
-            // Left hand, middle row
-            for (a, b, c) in kb.rows[1][0..4].iter().tuple_windows() {
-                let value = (*a, *b, *c);
+    // How much the n-grams involving either key change when moving from one
+    // placement of the keys to the other
+    fn delta(&self, before: &Positions, after: &Positions, a: u8, b: u8) -> i64 {
+        let mut delta = 0;
 
-                if value == forward {
-                    total += bytes.1 * TRIPLE_MIDDLE_INWARD;
-                    continue 'top;
-                }
+        for idx in &self.by_key[a as usize] {
+            let idx = *idx as usize;
+            delta += self.score(after, idx) - self.score(before, idx);
+        }
 
-                if value == backward {
-                    total += bytes.1 * TRIPLE_MIDDLE_OUTWARD;
-                    continue 'top;
-                }
+        for idx in &self.by_key[b as usize] {
+            let idx = *idx as usize;
+            if !self.ngram(idx).contains(&a) {
+                delta += self.score(after, idx) - self.score(before, idx);
             }
+        }
 
-            // Right hand, middle row
-            for (a, b, c) in kb.rows[1][6..].iter().tuple_windows() {
-                let value = (*a, *b, *c);
+        delta
+    }
+}
 
-                if value == backward {
-                    total += bytes.1 * TRIPLE_MIDDLE_INWARD;
-                    continue 'top;
-                }
-                if value == forward {
-                    total += bytes.1 * TRIPLE_MIDDLE_OUTWARD;
-                    continue 'top;
-                }
-            }
+// A layout along with where each key sits on it, so that swapping two keys only
+// needs to rescore the n-grams involving them
+#[derive(Clone)]
+struct ScoredKeyboard {
+    keyboard: Keyboard,
+    positions: Positions,
+    score: i64,
+    finger_counts: [i64; 8],
+}
 
-            // Left hand, top row
-            for (a, b, c) in kb.rows[0][0..4].iter().tuple_windows() {
-                let value = (*a, *b, *c);
+// A scoring rule applied to each n-gram of the given size, given the (row, col)
+// of each of its keys
+struct Metric {
+    name: &'static str,
+    size: usize,
+    enabled: bool,
+    // Whether the weight is multiplied by how often the n-gram occurs
+    scaled: bool,
+    score: fn(&[(usize, usize)]) -> i64,
+}
 
-                if value == forward {
-                    total += bytes.1 * TRIPLE_TOP_INWARD;
-                    continue 'top;
-                }
-                if value == backward {
-                    total += bytes.1 * TRIPLE_TOP_OUTWARD;
-                    continue 'top;
-                }
-            }
+const METRICS: &[Metric] = &[
+    Metric {
+        name: "singles",
+        size: 1,
+        enabled: true,
+        scaled: true,
+        score: score_single_row,
+    },
+    Metric {
+        name: "doubles",
+        size: 2,
+        enabled: true,
+        scaled: true,
+        score: score_double_roll,
+    },
+    Metric {
+        name: "triples",
+        size: 3,
+        enabled: true,
+        scaled: true,
+        score: score_triple_roll,
+    },
+    Metric {
+        name: "quadruples",
+        size: 4,
+        enabled: USE_QUADRUPLE_ROLL,
+        scaled: true,
+        score: score_quadruple_roll,
+    },
+    Metric {
+        name: "center column",
+        size: 1,
+        enabled: true,
+        scaled: true,
+        score: score_center_column,
+    },
+    Metric {
+        name: "pinkie",
+        size: 1,
+        enabled: true,
+        scaled: true,
+        score: score_pinkie,
+    },
+    Metric {
+        name: "finger curl",
+        size: 1,
+        enabled: true,
+        scaled: true,
+        score: score_finger_curl,
+    },
+    Metric {
+        name: "bottom right",
+        size: 1,
+        enabled: true,
+        scaled: false,
+        score: score_bottom_right,
+    },
+    Metric {
+        name: "two row move",
+        size: 2,
+        enabled: !USE_SCISSORS,
+        scaled: true,
+        score: score_two_row_move,
+    },
+    Metric {
+        name: "scissors",
+        size: 2,
+        enabled: USE_SCISSORS,
+        scaled: true,
+        score: score_scissors,
+    },
+    Metric {
+        name: "same finger",
+        size: 2,
+        enabled: true,
+        scaled: true,
+        score: score_same_finger,
+    },
+    Metric {
+        name: "lateral stretch",
+        size: 2,
+        enabled: true,
+        scaled: true,
+        score: score_lateral_stretch,
+    },
+    Metric {
+        name: "trigram class",
+        size: 3,
+        enabled: true,
+        scaled: true,
+        score: score_trigram_class,
+    },
+];
+
+// Whether the keys run along the given row, within the given columns, one column
+// apart. Returns Some(true) if they run left to right, and Some(false) if right to left
+fn row_run(coords: &[(usize, usize)], row: usize, cols: std::ops::Range<usize>) -> Option<bool> {
+    if coords.iter().any(|(r, c)| *r != row || !cols.contains(c)) {
+        return None;
+    }
 
-            // Right hand, top row
-            for (a, b, c) in kb.rows[0][6..].iter().tuple_windows() {
-                let value = (*a, *b, *c);
+    if coords.iter().tuple_windows().all(|(a, b)| b.1 == a.1 + 1) {
+        Some(true)
+    } else if coords.iter().tuple_windows().all(|(a, b)| a.1 == b.1 + 1) {
+        Some(false)
+    } else {
+        None
+    }
+}
 
-                if value == backward {
-                    total += bytes.1 * TRIPLE_MIDDLE_INWARD;
-                    continue 'top;
-                }
-                if value == forward {
-                    total += bytes.1 * TRIPLE_MIDDLE_OUTWARD;
-                    continue 'top;
-                }
-            }
+fn score_single_row(coords: &[(usize, usize)]) -> i64 {
+    match coords[0].0 {
+        0 => SINGLE_TOP,
+        1 => SINGLE_MIDDLE,
+        _ => SINGLE_BOTTOM,
+    }
+}
 
-            // Left hand, j-rolls
-            if kb.rows[1][0] == forward.0
-                && kb.rows[1][1] == forward.1
-                && kb.rows[2][2] == forward.2
-            {
-                total += bytes.1 * JROLL_INWARD;
-                continue;
-            }
+fn score_double_roll(coords: &[(usize, usize)]) -> i64 {
+    // Left hand, middle row
+    match row_run(coords, 1, 0..4) {
+        Some(true) => return DOUBLE_MIDDLE_INWARD,
+        Some(false) => return DOUBLE_MIDDLE_OUTWARD,
+        None => {}
+    }
 
-            if kb.rows[1][1] == forward.0
-                && kb.rows[1][2] == forward.1
-                && kb.rows[2][2] == forward.2
-            {
-                total += bytes.1 * JROLL_INWARD;
-                continue;
-            }
+    // Right hand, middle row
+    match row_run(coords, 1, 6..10) {
+        Some(false) => return DOUBLE_MIDDLE_INWARD,
+        Some(true) => return DOUBLE_MIDDLE_OUTWARD,
+        None => {}
+    }
 
-            if kb.rows[1][1] == forward.2
-                && kb.rows[1][2] == forward.1
-                && kb.rows[2][2] == forward.0
-            {
-                total += bytes.1 * JROLL_OUTWARD;
-                continue;
-            }
+    // Left hand, top row
+    match row_run(coords, 0, 0..4) {
+        Some(true) => return DOUBLE_TOP_INWARD,
+        Some(false) => return DOUBLE_TOP_OUTWARD,
+        None => {}
+    }
 
-            // Right hand, j-rolls
-            if kb.rows[1][9] == forward.0
-                && kb.rows[1][8] == forward.1
-                && kb.rows[2][6] == forward.2
-            {
-                total += bytes.1 * JROLL_INWARD;
-                continue;
-            }
+    // Right hand, top row
+    match row_run(coords, 0, 6..10) {
+        Some(false) => DOUBLE_MIDDLE_INWARD,
+        Some(true) => DOUBLE_MIDDLE_OUTWARD,
+        None => 0,
+    }
+}
 
-            if kb.rows[1][8] == forward.0
-                && kb.rows[1][7] == forward.1
-                && kb.rows[2][6] == forward.2
-            {
-                total += bytes.1 * JROLL_INWARD;
-                continue;
-            }
+fn score_triple_roll(coords: &[(usize, usize)]) -> i64 {
+    // Left hand, middle row
+    match row_run(coords, 1, 0..4) {
+        Some(true) => return TRIPLE_MIDDLE_INWARD,
+        Some(false) => return TRIPLE_MIDDLE_OUTWARD,
+        None => {}
+    }
 
-            if kb.rows[1][8] == forward.2
-                && kb.rows[1][7] == forward.1
-                && kb.rows[2][6] == forward.0
-            {
-                total += bytes.1 * JROLL_OUTWARD;
-                continue;
-            }
-        }
-        total
+    // Right hand, middle row
+    match row_run(coords, 1, 6..10) {
+        Some(false) => return TRIPLE_MIDDLE_INWARD,
+        Some(true) => return TRIPLE_MIDDLE_OUTWARD,
+        None => {}
     }
 
-    fn score_quadruples(&self, kb: &Keyboard) -> i64 {
-        let mut total = 0;
+    // Left hand, top row
+    match row_run(coords, 0, 0..4) {
+        Some(true) => return TRIPLE_TOP_INWARD,
+        Some(false) => return TRIPLE_TOP_OUTWARD,
+        None => {}
+    }
 
-        'top: for bytes in &self.quadruple_byte {
-            let forward = (bytes.0[0], bytes.0[1], bytes.0[2], bytes.0[3]);
-            let backward = (bytes.0[3], bytes.0[2], bytes.0[1], bytes.0[0]);
+    // Right hand, top row
+    match row_run(coords, 0, 6..10) {
+        Some(false) => return TRIPLE_MIDDLE_INWARD,
+        Some(true) => return TRIPLE_MIDDLE_OUTWARD,
+        None => {}
+    }
 
-            // Left hand, middle row
-            for (a, b, c, d) in kb.rows[1][0..4].iter().tuple_windows() {
-                let value = (*a, *b, *c, *d);
+    match coords {
+        // Left hand, j-rolls
+        [(1, 0), (1, 1), (2, 2)] => JROLL_INWARD,
+        [(1, 1), (1, 2), (2, 2)] => JROLL_INWARD,
+        [(2, 2), (1, 2), (1, 1)] => JROLL_OUTWARD,
 
-                if value == forward {
-                    total += bytes.1 * QUADRUPLE_MIDDLE_INWARD;
-                    continue 'top;
-                }
+        // Right hand, j-rolls
+        [(1, 9), (1, 8), (2, 6)] => JROLL_INWARD,
+        [(1, 8), (1, 7), (2, 6)] => JROLL_INWARD,
+        [(2, 6), (1, 7), (1, 8)] => JROLL_OUTWARD,
 
-                if value == backward {
-                    total += bytes.1 * QUADRUPLE_MIDDLE_OUTWARD;
-                    continue 'top;
-                }
-            }
+        _ => 0,
+    }
+}
 
-            // Right hand, middle row
-            for (a, b, c, d) in kb.rows[1][6..].iter().tuple_windows() {
-                let value = (*a, *b, *c, *d);
+fn score_quadruple_roll(coords: &[(usize, usize)]) -> i64 {
+    // Left hand, middle row
+    match row_run(coords, 1, 0..4) {
+        Some(true) => return QUADRUPLE_MIDDLE_INWARD,
+        Some(false) => return QUADRUPLE_MIDDLE_OUTWARD,
+        None => {}
+    }
 
-                if value == backward {
-                    total += bytes.1 * QUADRUPLE_MIDDLE_INWARD;
-                    continue 'top;
-                }
-                if value == forward {
-                    total += bytes.1 * QUADRUPLE_MIDDLE_OUTWARD;
-                    continue 'top;
-                }
-            }
+    // Right hand, middle row
+    match row_run(coords, 1, 6..10) {
+        Some(false) => return QUADRUPLE_MIDDLE_INWARD,
+        Some(true) => return QUADRUPLE_MIDDLE_OUTWARD,
+        None => {}
+    }
 
-            // Left hand, top row
-            for (a, b, c, d) in kb.rows[0][0..4].iter().tuple_windows() {
-                let value = (*a, *b, *c, *d);
+    // Left hand, top row
+    match row_run(coords, 0, 0..4) {
+        Some(true) => return QUADRUPLE_TOP_INWARD,
+        Some(false) => return QUADRUPLE_TOP_OUTWARD,
+        None => {}
+    }
 
-                if value == forward {
-                    total += bytes.1 * QUADRUPLE_TOP_INWARD;
-                    continue 'top;
-                }
-                if value == backward {
-                    total += bytes.1 * QUADRUPLE_TOP_OUTWARD;
-                    continue 'top;
-                }
-            }
+    // Right hand, top row
+    match row_run(coords, 0, 6..10) {
+        Some(false) => return QUADRUPLE_MIDDLE_INWARD,
+        Some(true) => return QUADRUPLE_MIDDLE_OUTWARD,
+        None => {}
+    }
 
-            // Right hand, top row
-            for (a, b, c, d) in kb.rows[0][6..].iter().tuple_windows() {
-                let value = (*a, *b, *c, *d);
+    match coords {
+        [(1, 1), (1, 2), (1, 3), (2, 3)] => LONG_JROLL_INWARD,
+        [(1, 9), (1, 8), (1, 7), (2, 6)] => LONG_JROLL_INWARD,
+        _ => 0,
+    }
+}
 
-                if value == backward {
-                    total += bytes.1 * QUADRUPLE_MIDDLE_INWARD;
-                    continue 'top;
-                }
-                if value == forward {
-                    total += bytes.1 * QUADRUPLE_MIDDLE_OUTWARD;
-                    continue 'top;
-                }
-            }
+fn score_center_column(coords: &[(usize, usize)]) -> i64 {
+    match coords[0] {
+        (0, 4 | 5) => CENTER_TOP_PENALTY,
+        (1, 4 | 5) => CENTER_MIDDLE_PENALTY,
+        (2, 4 | 5) => CENTER_BOTTOM_PENALTY,
+        _ => 0,
+    }
+}
 
-            if kb.rows[1][1] == forward.0
-                && kb.rows[1][2] == forward.1
-                && kb.rows[1][3] == forward.2
-                && kb.rows[2][3] == forward.3
-            {
-                total += bytes.1 * LONG_JROLL_INWARD;
-                continue;
-            }
-            if kb.rows[1][9] == forward.0
-                && kb.rows[1][8] == forward.1
-                && kb.rows[1][7] == forward.2
-                && kb.rows[2][6] == forward.3
-            {
-                total += bytes.1 * LONG_JROLL_INWARD;
-                continue;
-            }
+fn score_pinkie(coords: &[(usize, usize)]) -> i64 {
+    match coords[0] {
+        (0, 0) | (2, 0) | (2, 9) | (0, 9) => PINKIE_PENALTY,
+        _ => 0,
+    }
+}
+
+fn score_finger_curl(coords: &[(usize, usize)]) -> i64 {
+    match coords[0] {
+        (2, 0) | (2, 1) | (2, 9) | (2, 8) => MINOR_FINGER_CURL_PENALTY,
+        _ => 0,
+    }
+}
+
+// Protect the bottom three keys so we can use what is usually there
+// This isn't strictly necessary but helps with adapting the layout
+fn score_bottom_right(coords: &[(usize, usize)]) -> i64 {
+    match coords[0] {
+        (2, 7) | (2, 8) | (2, 9) => BOTTOM_RIGHT_PENALTY,
+        _ => 0,
+    }
+}
+
+// Penalty for jumping between top and bottom rows
+fn score_two_row_move(coords: &[(usize, usize)]) -> i64 {
+    let (from_row, to_row) = (coords[0].0, coords[1].0);
+
+    if (from_row == 0 && to_row == 2) || (from_row == 2 && to_row == 0) {
+        TWO_ROW_MOVE_PENALTY
+    } else {
+        0
+    }
+}
+
+fn score_scissors(coords: &[(usize, usize)]) -> i64 {
+    if is_full_scissor(coords[0], coords[1]) {
+        FULL_SCISSOR_PENALTY
+    } else if is_half_scissor(coords[0], coords[1]) {
+        HALF_SCISSOR_PENALTY
+    } else {
+        0
+    }
+}
+
+fn score_same_finger(coords: &[(usize, usize)]) -> i64 {
+    let ((from_row, from_col), (to_row, to_col)) = (coords[0], coords[1]);
+
+    if from_col == to_col && from_row != to_row {
+        SAME_FINGER_PENALTY
+    } else {
+        0
+    }
+}
+
+// Penalty for stretching between neighbouring fingers, eg) the middle finger
+// followed by the index finger reaching into the center column
+fn score_lateral_stretch(coords: &[(usize, usize)]) -> i64 {
+    if is_lateral_stretch(coords[0].1, coords[1].1) {
+        LATERAL_STRETCH_PENALTY
+    } else {
+        0
+    }
+}
+
+fn score_trigram_class(coords: &[(usize, usize)]) -> i64 {
+    TrigramClass::classify((coords[0].1, coords[1].1, coords[2].1)).weight()
+}
+
+fn finger_load_penalty(counts: &[i64; 8], total: i64) -> i64 {
+    let total = total as f64;
+
+    let mut finger_deviation = 0.0;
+    for (count, target) in counts.iter().zip(FINGER_TARGET_LOAD) {
+        finger_deviation += (*count as f64 - target * total / 100.0).abs();
+    }
+
+    let left_target: f64 = FINGER_TARGET_LOAD[0..4].iter().sum();
+    let left_count: i64 = counts[0..4].iter().sum();
+    let hand_deviation = (left_count as f64 - left_target * total / 100.0).abs();
+
+    // Whatever the left hand is missing, the right hand has in excess, so count both
+    FINGER_LOAD_PENALTY * finger_deviation as i64 + HAND_LOAD_PENALTY * 2 * hand_deviation as i64
+}
+
+// Where the score of a layout comes from, metric by metric
+struct ScoreBreakdown {
+    total: i64,
+    metrics: Vec<(&'static str, i64)>,
+    bonuses: Vec<(String, &'static str, i64)>,
+    penalties: Vec<(String, &'static str, i64)>,
+}
+
+impl Display for ScoreBreakdown {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (name, score) in &self.metrics {
+            writeln!(f, "    {:<16}{:>12}", name, score)?;
+        }
+        writeln!(f, "    {:<16}{:>12}", "total", self.total)?;
+
+        writeln!(f, "    top bonuses:")?;
+        for (ngram, name, score) in &self.bonuses {
+            writeln!(f, "        {:<6}{:<16}{:>12}", ngram, name, score)?;
+        }
+
+        writeln!(f, "    top penalties:")?;
+        for (ngram, name, score) in &self.penalties {
+            writeln!(f, "        {:<6}{:<16}{:>12}", ngram, name, score)?;
+        }
+        Ok(())
+    }
+}
+
+impl Scorer {
+    pub fn new(
+        single_byte: Vec<(u8, i64)>,
+        double_byte: Vec<(Vec<u8>, i64)>,
+        triple_byte: Vec<(Vec<u8>, i64)>,
+        quadruple_byte: Vec<(Vec<u8>, i64)>,
+    ) -> Scorer {
+        let mut scorer = Scorer {
+            single_byte,
+            double_byte,
+            triple_byte,
+            quadruple_byte,
+            tables: Arc::new(vec![]),
+            single_total: 0,
+        };
+
+        scorer.tables = Arc::new(
+            (1..=4)
+                .map(|size| NgramTable::new(size, scorer.ngrams(size)))
+                .collect(),
+        );
+        scorer.single_total = scorer.single_byte.iter().map(|byte| byte.1).sum();
+
+        scorer
+    }
+
+    // The n-grams of the given size along with how often they occur
+    fn ngrams(&self, size: usize) -> Vec<(&[u8], i64)> {
+        match size {
+            1 => self
+                .single_byte
+                .iter()
+                .map(|(k, v)| (std::slice::from_ref(k), *v))
+                .collect(),
+            2 => self.double_byte.iter().map(|(k, v)| (&k[..], *v)).collect(),
+            3 => self.triple_byte.iter().map(|(k, v)| (&k[..], *v)).collect(),
+            4 => self
+                .quadruple_byte
+                .iter()
+                .map(|(k, v)| (&k[..], *v))
+                .collect(),
+            _ => vec![],
         }
-        total
     }
 
-    fn score_penalties(&self, kb: &Keyboard) -> i64 {
+    pub fn trigram_stats(&self, kb: &Keyboard) -> TrigramStats {
+        let positions = kb.positions();
+        let mut counts = [0i64; TrigramClass::ALL.len()];
         let mut total: i64 = 0;
 
+        for triple in &self.triple_byte {
+            let (_, a) = position_coords(positions[triple.0[0] as usize]);
+            let (_, b) = position_coords(positions[triple.0[1] as usize]);
+            let (_, c) = position_coords(positions[triple.0[2] as usize]);
+            let class = TrigramClass::classify((a, b, c));
+
+            counts[class as usize] += triple.1;
+            total += triple.1;
+        }
+
+        let percentages = TrigramClass::ALL
+            .iter()
+            .map(|class| {
+                let percentage = if total > 0 {
+                    counts[*class as usize] as f64 * 100.0 / total as f64
+                } else {
+                    0.0
+                };
+                (*class, percentage)
+            })
+            .collect();
+
+        TrigramStats { percentages }
+    }
+
+    fn finger_counts(&self, positions: &Positions) -> [i64; 8] {
+        let mut counts = [0i64; 8];
+
         for byte in &self.single_byte {
-            if kb.rows[0][4] == byte.0 || kb.rows[0][5] == byte.0 {
-                total += byte.1 * CENTER_TOP_PENALTY;
-            }
-            if kb.rows[1][4] == byte.0 || kb.rows[1][5] == byte.0 {
-                total += byte.1 * CENTER_MIDDLE_PENALTY;
-            }
-            if kb.rows[2][4] == byte.0 || kb.rows[2][5] == byte.0 {
-                total += byte.1 * CENTER_BOTTOM_PENALTY;
-            }
-            if kb.rows[0][0] == byte.0
-                || kb.rows[2][0] == byte.0
-                || kb.rows[2][9] == byte.0
-                || kb.rows[0][9] == byte.0
-            {
-                total += byte.1 * PINKIE_PENALTY;
-            }
-            if kb.rows[2][0] == byte.0
-                || kb.rows[2][1] == byte.0
-                || kb.rows[2][9] == byte.0
-                || kb.rows[2][8] == byte.0
-            {
-                total += byte.1 * MINOR_FINGER_CURL_PENALTY;
-            }
+            let (_, col) = position_coords(positions[byte.0 as usize]);
 
-            // Protect the bottom three keys so we can use what is usually there
-            // This isn't strictly necessary but helps with adapting the layout
-            if kb.rows[2][7] == byte.0 || kb.rows[2][8] == byte.0 || kb.rows[2][9] == byte.0 {
-                total += BOTTOM_RIGHT_PENALTY
-            }
+            counts[FINGER_MAP[col]] += byte.1;
         }
 
-        // Penalty for jumping between top and bottom rows
-        for double in &self.double_byte {
-            let (from_row, from_col) = kb.find_key(double.0[0]);
-            let (to_row, to_col) = kb.find_key(double.0[1]);
+        counts
+    }
 
-            if (from_row == 0 && to_row == 2) || (from_row == 2 && to_row == 0) {
-                total += double.1 * TWO_ROW_MOVE_PENALTY;
+    pub fn finger_load(&self, kb: &Keyboard) -> FingerLoad {
+        let counts = self.finger_counts(&kb.positions());
+
+        let mut fingers = [0.0; 8];
+        if self.single_total > 0 {
+            for (finger, count) in fingers.iter_mut().zip(counts) {
+                *finger = count as f64 * 100.0 / self.single_total as f64;
             }
+        }
+
+        let left: f64 = fingers[0..4].iter().sum();
+        let right: f64 = fingers[4..].iter().sum();
+
+        FingerLoad {
+            fingers,
+            hands: [left, right],
+        }
+    }
+
+    // Percentage of key presses that are the given key
+    pub fn key_frequency(&self, key: u8) -> f64 {
+        let count = self
+            .single_byte
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map_or(0, |(_, count)| *count);
+
+        if self.single_total > 0 {
+            count as f64 * 100.0 / self.single_total as f64
+        } else {
+            0.0
+        }
+    }
+
+    // The bigrams whose keys sit where `matches` accepts, with the percentage of
+    // all bigrams each makes up, most frequent first
+    fn bigram_frequencies(
+        &self,
+        kb: &Keyboard,
+        matches: impl Fn((usize, usize), (usize, usize)) -> bool,
+    ) -> Vec<(String, f64)> {
+        let positions = kb.positions();
+        let total: i64 = self.double_byte.iter().map(|(_, count)| count).sum();
+
+        let mut bigrams: Vec<_> = self
+            .double_byte
+            .iter()
+            .filter(|(bytes, _)| {
+                matches(
+                    position_coords(positions[bytes[0] as usize]),
+                    position_coords(positions[bytes[1] as usize]),
+                )
+            })
+            .map(|(bytes, count)| {
+                let bigram: String = bytes.iter().map(|b| *b as char).collect();
+                (bigram, *count as f64 * 100.0 / total as f64)
+            })
+            .collect();
+
+        bigrams.sort_by(|a, b| b.1.total_cmp(&a.1));
+        bigrams
+    }
+
+    // Bigrams typed twice with the same finger
+    pub fn same_finger_bigrams(&self, kb: &Keyboard) -> Vec<(String, f64)> {
+        self.bigram_frequencies(kb, |(_, from_col), (_, to_col)| {
+            FINGER_MAP[from_col] == FINGER_MAP[to_col]
+        })
+    }
+
+    // Bigrams rolled across two fingers of the same hand
+    pub fn roll_bigrams(&self, kb: &Keyboard) -> Vec<(String, f64)> {
+        self.bigram_frequencies(kb, |(_, from_col), (_, to_col)| {
+            same_hand(from_col, to_col) && FINGER_MAP[from_col] != FINGER_MAP[to_col]
+        })
+    }
+
+    fn score_positions(&self, positions: &Positions) -> i64 {
+        count_evaluation();
+
+        let mut total: i64 = 0;
 
-            if from_col == to_col && from_row != to_row {
-                total += double.1 * SAME_FINGER_PENALTY;
+        for table in self.tables.iter() {
+            for idx in 0..table.counts.len() {
+                total += table.score(positions, idx);
             }
         }
 
+        total += finger_load_penalty(&self.finger_counts(positions), self.single_total);
+
         total
     }
 
+    // The n-grams counted from the corpus, as a fingerprint for checkpoints
+    pub fn corpus_fingerprint(&self) -> u64 {
+        checkpoint::fingerprint(&(
+            &self.single_byte,
+            &self.double_byte,
+            &self.triple_byte,
+            &self.quadruple_byte,
+        ))
+    }
+
+    // Everything the n-grams are scored with, as a fingerprint for checkpoints
+    pub fn weights_fingerprint(&self) -> u64 {
+        let tables: Vec<_> = self
+            .tables
+            .iter()
+            .map(|table| (&table.weights, &table.flat_weights))
+            .collect();
+        let target_load = FINGER_TARGET_LOAD.map(f64::to_bits);
+
+        checkpoint::fingerprint(&(tables, target_load, FINGER_LOAD_PENALTY, HAND_LOAD_PENALTY))
+    }
+
     pub fn score_keyboard(&self, kb: &Keyboard) -> i64 {
-        let mut total: i64 = 0;
+        self.score_positions(&kb.positions())
+    }
+
+    pub fn score_cached(&self, keyboard: Keyboard) -> ScoredKeyboard {
+        let positions = keyboard.positions();
 
-        total += self.score_singles(kb);
-        total += self.score_doubles(kb);
-        total += self.score_triples(kb);
-        if USE_QUADRUPLE_ROLL {
-            total += self.score_quadruples(kb);
+        ScoredKeyboard {
+            score: self.score_positions(&positions),
+            finger_counts: self.finger_counts(&positions),
+            positions,
+            keyboard,
         }
+    }
 
-        total += self.score_penalties(kb);
+    // Where the keys would sit, and how the finger counts would change, after
+    // swapping the keys at the two positions
+    fn swapped(
+        &self,
+        current: &ScoredKeyboard,
+        from: (usize, usize),
+        to: (usize, usize),
+    ) -> (Positions, [i64; 8]) {
+        let a = current.keyboard.rows[from.0][from.1];
+        let b = current.keyboard.rows[to.0][to.1];
+
+        // Set these explicitly, as the filler keys appear more than once
+        let mut positions = current.positions;
+        positions[a as usize] = (to.0 * 10 + to.1) as u8;
+        positions[b as usize] = (from.0 * 10 + from.1) as u8;
+
+        let mut counts = current.finger_counts;
+        for (key, count) in &self.single_byte {
+            if *key == a {
+                counts[FINGER_MAP[from.1]] -= count;
+                counts[FINGER_MAP[to.1]] += count;
+            } else if *key == b {
+                counts[FINGER_MAP[to.1]] -= count;
+                counts[FINGER_MAP[from.1]] += count;
+            }
+        }
 
-        total
+        (positions, counts)
+    }
+
+    // How much the score would change by swapping the keys at the two positions,
+    // only rescoring the n-grams that involve those keys
+    pub fn swap_delta(
+        &self,
+        current: &ScoredKeyboard,
+        from: (usize, usize),
+        to: (usize, usize),
+    ) -> i64 {
+        let a = current.keyboard.rows[from.0][from.1];
+        let b = current.keyboard.rows[to.0][to.1];
+
+        if a == b {
+            return 0;
+        }
+
+        count_evaluation();
+
+        let (positions, counts) = self.swapped(current, from, to);
+
+        let mut delta: i64 = 0;
+        for table in self.tables.iter() {
+            delta += table.delta(&current.positions, &positions, a, b);
+        }
+
+        delta += finger_load_penalty(&counts, self.single_total)
+            - finger_load_penalty(&current.finger_counts, self.single_total);
+
+        delta
+    }
+
+    // Swap the keys at the two positions and update the cached score
+    pub fn apply_swap(
+        &self,
+        current: &mut ScoredKeyboard,
+        from: (usize, usize),
+        to: (usize, usize),
+    ) {
+        current.score += self.swap_delta(current, from, to);
+
+        let (positions, counts) = self.swapped(current, from, to);
+        current.positions = positions;
+        current.finger_counts = counts;
+        current.keyboard.swap(from, to);
+    }
+
+    pub fn score_breakdown(&self, kb: &Keyboard) -> ScoreBreakdown {
+        let positions = kb.positions();
+        let mut total: i64 = 0;
+        let mut metrics = vec![];
+        let mut ngrams = vec![];
+
+        for metric in METRICS.iter().filter(|metric| metric.enabled) {
+            let mut metric_total: i64 = 0;
+
+            for (bytes, count) in self.ngrams(metric.size) {
+                let coords: Vec<_> = bytes
+                    .iter()
+                    .map(|byte| position_coords(positions[*byte as usize]))
+                    .collect();
+
+                let mut score = (metric.score)(&coords);
+                if metric.scaled {
+                    score *= count;
+                }
+
+                if score != 0 {
+                    let ngram: String = bytes.iter().map(|b| *b as char).collect();
+                    ngrams.push((ngram, metric.name, score));
+                }
+                metric_total += score;
+            }
+
+            metrics.push((metric.name, metric_total));
+            total += metric_total;
+        }
+
+        let finger_load = finger_load_penalty(&self.finger_counts(&positions), self.single_total);
+        metrics.push(("finger load", finger_load));
+        total += finger_load;
+
+        ngrams.sort_by_key(|(_, _, score)| *score);
+
+        let penalties = ngrams
+            .iter()
+            .take_while(|(_, _, score)| *score < 0)
+            .take(BREAKDOWN_TOP_NGRAMS)
+            .cloned()
+            .collect();
+
+        let bonuses = ngrams
+            .iter()
+            .rev()
+            .take_while(|(_, _, score)| *score > 0)
+            .take(BREAKDOWN_TOP_NGRAMS)
+            .cloned()
+            .collect();
+
+        ScoreBreakdown {
+            total,
+            metrics,
+            bonuses,
+            penalties,
+        }
     }
 
     pub fn debug(&self) {
@@ -529,57 +1490,67 @@ impl Scorer {
     }
 }
 
-fn random_swap(kb: &mut Keyboard) {
-    let from_row = (rand::thread_rng().next_u64() % 3) as usize;
-    let from_col = (rand::thread_rng().next_u64() % 10) as usize;
-
-    let to_row = (rand::thread_rng().next_u64() % 3) as usize;
-    let to_col = (rand::thread_rng().next_u64() % 10) as usize;
-
-    let prev = kb.rows[to_row][to_col];
-    kb.rows[to_row][to_col] = kb.rows[from_row][from_col];
-    kb.rows[from_row][from_col] = prev;
+fn same_hand(from_col: usize, to_col: usize) -> bool {
+    (from_col < 5) == (to_col < 5)
 }
 
-fn find_keyboard(scorer: &Scorer) -> (i64, Keyboard) {
-    let mut keyboard = Keyboard::random_layout();
+// How far a finger is from the outside of its hand, from pinkie (0) to index (3)
+fn finger_rank(col: usize) -> usize {
+    if col < 5 {
+        FINGER_MAP[col]
+    } else {
+        7 - FINGER_MAP[col]
+    }
+}
 
-    let mut current_score = scorer.score_keyboard(&keyboard);
-    let mut time_since_last_improvement = 0;
+fn adjacent_fingers(from_col: usize, to_col: usize) -> bool {
+    same_hand(from_col, to_col) && FINGER_MAP[from_col].abs_diff(FINGER_MAP[to_col]) == 1
+}
 
-    loop {
-        time_since_last_improvement += 1;
+// Neighbouring fingers on the same hand that are more than one column apart
+fn is_lateral_stretch(from_col: usize, to_col: usize) -> bool {
+    adjacent_fingers(from_col, to_col) && from_col.abs_diff(to_col) > 1
+}
 
-        let mut new_keyboard = keyboard.clone();
+// Neighbouring fingers jumping between the top and bottom rows
+fn is_full_scissor(from: (usize, usize), to: (usize, usize)) -> bool {
+    adjacent_fingers(from.1, to.1) && from.0.abs_diff(to.0) == 2
+}
 
-        random_swap(&mut new_keyboard);
+// Neighbouring fingers one row apart, with the shorter finger reaching up
+fn is_half_scissor(from: (usize, usize), to: (usize, usize)) -> bool {
+    if !adjacent_fingers(from.1, to.1) || from.0.abs_diff(to.0) != 1 {
+        return false;
+    }
 
-        let new_score = scorer.score_keyboard(&new_keyboard);
+    let (upper, lower) = if from.0 < to.0 {
+        (from, to)
+    } else {
+        (to, from)
+    };
 
-        if new_score > current_score {
-            keyboard = new_keyboard;
-            current_score = new_score;
-            time_since_last_improvement = 0;
-        }
+    FINGER_LENGTH[FINGER_MAP[upper.1]] < FINGER_LENGTH[FINGER_MAP[lower.1]]
+}
 
-        if time_since_last_improvement >= FAILED_TO_IMPROVE_LIMIT {
-            break;
-        }
+fn write_svg(scorer: &Scorer, kb: &Keyboard, path: &Path) {
+    if let Err(e) = std::fs::write(path, svg::render_svg(scorer, kb)) {
+        eprintln!("Couldn't write {}: {}", path.display(), e);
     }
-
-    (current_score, keyboard)
 }
 
 fn main() {
+    let mut options = options::parse_args();
+
+    if let Some((format, kb)) = &options.export {
+        print!("{}", export::export(kb, *format));
+        return;
+    }
+
     let mut input = vec![];
-    let mut debug = false;
+    let breakdown = options.breakdown;
 
     println!("Loading in corpus...");
-    for file in std::env::args().skip(1) {
-        if file == "--debug" {
-            debug = true;
-            continue;
-        }
+    for file in &options.corpus {
         let file = std::fs::File::open(file).unwrap();
 
         let mut handle = file.take(100 * 1024 * 1024);
@@ -599,7 +1570,7 @@ fn main() {
     // Single byte counts
     let mut single_byte: HashMap<u8, i64> = HashMap::new();
     for a in input.iter() {
-        if (b'a'..=b'z').contains(a) {
+        if a.is_ascii_lowercase() {
             *single_byte.entry(*a).or_default() += 1;
         }
     }
@@ -608,7 +1579,7 @@ fn main() {
     // Double byte counts
     let mut double_byte: HashMap<Vec<u8>, i64> = HashMap::new();
     for (a, b) in input.iter().tuple_windows() {
-        if (b'a'..=b'z').contains(a) && (b'a'..=b'z').contains(b) && a != b {
+        if a.is_ascii_lowercase() && b.is_ascii_lowercase() && a != b {
             let item = vec![*a, *b];
             *double_byte.entry(item).or_default() += 1;
         }
@@ -618,9 +1589,9 @@ fn main() {
     // Triple byte counts
     let mut triple_byte: HashMap<Vec<u8>, i64> = HashMap::new();
     for (a, b, c) in input.iter().tuple_windows() {
-        if (b'a'..=b'z').contains(a)
-            && (b'a'..=b'z').contains(b)
-            && (b'a'..=b'z').contains(c)
+        if a.is_ascii_lowercase()
+            && b.is_ascii_lowercase()
+            && c.is_ascii_lowercase()
             && ![b, c].contains(&a)
             && b != c
         {
@@ -634,10 +1605,10 @@ fn main() {
     // Quadruple byte counts
     let mut quadruple_byte: HashMap<Vec<u8>, i64> = HashMap::new();
     for (a, b, c, d) in input.iter().tuple_windows() {
-        if (b'a'..=b'z').contains(a)
-            && (b'a'..=b'z').contains(b)
-            && (b'a'..=b'z').contains(c)
-            && (b'a'..=b'z').contains(d)
+        if a.is_ascii_lowercase()
+            && b.is_ascii_lowercase()
+            && c.is_ascii_lowercase()
+            && d.is_ascii_lowercase()
             && ![b, c, d].contains(&a)
             && ![c, d].contains(&b)
             && c != d
@@ -648,65 +1619,99 @@ fn main() {
         }
     }
 
+    // Ties are broken by the n-gram itself, so the same ones are kept on every run
     let single_byte: Vec<_> = single_byte
         .into_iter()
-        .sorted_by(|a, b| Ord::cmp(&b.1, &a.1))
+        .sorted_by(|a, b| Ord::cmp(&b.1, &a.1).then(Ord::cmp(&a.0, &b.0)))
         .take(MAX_SAMPLES_PER_CATEGORY)
         .collect();
 
     let double_byte: Vec<_> = double_byte
         .into_iter()
-        .sorted_by(|a, b| Ord::cmp(&b.1, &a.1))
+        .sorted_by(|a, b| Ord::cmp(&b.1, &a.1).then(Ord::cmp(&a.0, &b.0)))
         .take(MAX_SAMPLES_PER_CATEGORY)
         .collect();
 
     let triple_byte: Vec<_> = triple_byte
         .into_iter()
-        .sorted_by(|a, b| Ord::cmp(&b.1, &a.1))
+        .sorted_by(|a, b| Ord::cmp(&b.1, &a.1).then(Ord::cmp(&a.0, &b.0)))
         .take(MAX_SAMPLES_PER_CATEGORY)
         .collect();
 
     let quadruple_byte: Vec<_> = quadruple_byte
         .into_iter()
-        .sorted_by(|a, b| Ord::cmp(&b.1, &a.1))
+        .sorted_by(|a, b| Ord::cmp(&b.1, &a.1).then(Ord::cmp(&a.0, &b.0)))
         .take(MAX_SAMPLES_PER_CATEGORY)
         .collect();
 
-    let scorer = Scorer {
-        single_byte,
-        double_byte,
-        triple_byte,
-        quadruple_byte,
-    };
+    let scorer = Scorer::new(single_byte, double_byte, triple_byte, quadruple_byte);
 
-    if debug {
+    if options.debug {
         scorer.debug();
         return;
     }
 
-    let standard_keyboards = vec![
-        Keyboard::qwerty(),
-        Keyboard::dvorak(),
-        Keyboard::colemak(),
-        Keyboard::workman(),
-    ];
+    // Carry on from a checkpoint, with the search set up as it was
+    let resumed = options.resume.as_ref().map(|path| {
+        checkpoint::load_checkpoint(path, &scorer).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        })
+    });
+    if let Some(checkpoint) = &resumed {
+        options.optimiser = checkpoint.optimiser;
+        options.threads = checkpoint.threads;
+        options.seed = checkpoint.seed;
+        options.template = checkpoint.template.clone();
+    }
+    let resumes = resumed
+        .as_ref()
+        .map_or(0, |checkpoint| checkpoint.resumes + 1);
+    // The seed the workers are started with, which is what a run is logged with
+    let worker_seed = checkpoint::resumed_seed(options.seed, resumes);
+    // A resumed search keeps saving to its checkpoint, unless given another
+    let checkpoint_path = options.checkpoint.clone().or(options.resume.clone());
+
+    let mut standard_keyboards = Keyboard::standard_layouts();
+    standard_keyboards.extend(options.layouts.iter().cloned());
 
     // Show the score for the standard keyboards
     // for this round of scoring
-    let mut dictionary = HashMap::new();
-    for kb in &standard_keyboards {
-        let score = scorer.score_keyboard(kb);
-
-        dictionary.insert(kb.name.clone(), score);
-    }
-
-    let mut result: Vec<_> = dictionary.into_iter().collect();
+    let mut result: Vec<_> = standard_keyboards
+        .iter()
+        .map(|kb| (scorer.score_keyboard(kb), kb))
+        .collect();
 
-    result.sort_by(|a, b| (a.1).partial_cmp(&b.1).unwrap());
+    result.sort_by_key(|(score, _)| *score);
 
     println!("algorithm: {}", ALGORITHM_VERSION);
-    for keyboard in result {
-        println!("{}: {}", keyboard.0, keyboard.1);
+    println!("seed: {}", options.seed);
+    if let Some(path) = &options.resume {
+        println!("resumed from: {}", path.display());
+        println!("resumed seed: {}", worker_seed);
+        if !options.optimiser.saves_state() {
+            println!(
+                "{} doesn't save its searches, so they start afresh",
+                options.optimiser.name()
+            );
+        }
+    }
+    for (score, kb) in result {
+        println!("{} ({}): {}", kb.name, kb.geometry, score);
+        println!("    {}", scorer.trigram_stats(kb));
+        println!("    {}", scorer.finger_load(kb));
+        if options.heatmap {
+            print!(
+                "{}",
+                heatmap::render_heatmap(&scorer, kb, options.heatmap_overlay)
+            );
+        }
+        if let Some(dir) = &options.svg {
+            write_svg(&scorer, kb, &dir.join(format!("{}.svg", kb.name)));
+        }
+        if breakdown {
+            print!("{}", scorer.score_breakdown(kb));
+        }
     }
 
     // println!("Finding a keyboard...");
@@ -715,40 +1720,406 @@ fn main() {
     // let score = scorer.score_keyboard(&kb);
     // println!("{}|{}", score, kb);
 
-    let (sender, receiver) = channel();
-
-    for _ in 1..10 {
-        let sender = sender.clone();
-        let scorer = scorer.clone();
-
-        thread::spawn(move || loop {
-            let (score, kb) = find_keyboard(&scorer);
-
-            let _ = sender.send((score, kb));
+    let progress = Arc::new(match &resumed {
+        Some(checkpoint) => Progress::resuming(checkpoint.evaluations, checkpoint.state.clone()),
+        None => Progress::default(),
+    });
+    {
+        let progress = progress.clone();
+        let handler = ctrlc::set_handler(move || {
+            if progress.stopping() {
+                std::process::exit(130);
+            }
+            println!();
+            println!("Stopping once the workers finish their current search, press Ctrl-C again to quit now");
+            progress.stop();
         });
+        if let Err(e) = handler {
+            eprintln!("Couldn't handle Ctrl-C: {}", e);
+        }
     }
 
-    let mut best = i64::MIN;
-
-    loop {
-        let (score, kb) = receiver.recv().unwrap();
-
-        if score > best {
+    let mut results = options.optimiser.start(
+        &scorer,
+        &options.template,
+        options.threads,
+        worker_seed,
+        progress.clone(),
+    );
+
+    // Budgets count the time spent before any checkpoint this resumes
+    let before = resumed
+        .as_ref()
+        .map_or(Duration::ZERO, |checkpoint| checkpoint.elapsed);
+    let started = Instant::now();
+    let mut last_improvement = started;
+    let mut last_checkpoint = started;
+    // The best few different layouts, best first, for the summary at the end
+    let mut best_layouts: Vec<(i64, Keyboard)> =
+        resumed.map_or(vec![], |checkpoint| checkpoint.best);
+
+    let record = |found: Found, best_layouts: &mut Vec<(i64, Keyboard)>| {
+        let (score, kb) = (found.score, found.keyboard);
+        let improved = best_layouts.first().is_none_or(|(best, _)| score > *best);
+
+        if improved {
             println!("New best: {}|{}|{}", ALGORITHM_VERSION, score, kb);
-            best = score;
+            if let Some(stats) = &found.stats {
+                println!("    {}", stats);
+            }
+            if options.heatmap {
+                print!(
+                    "{}",
+                    heatmap::render_heatmap(&scorer, &kb, options.heatmap_overlay)
+                );
+            }
+            if let Some(dir) = &options.svg {
+                write_svg(&scorer, &kb, &dir.join("best.svg"));
+            }
+            if breakdown {
+                print!("{}", scorer.score_breakdown(&kb));
+            }
         }
 
         let mut file = OpenOptions::new()
-            .write(true)
             .create(true)
             .append(true)
             .open("output.log")
             .unwrap();
 
-        if let Err(e) = writeln!(file, "{}|{}|{}", ALGORITHM_VERSION, score, kb) {
+        if let Err(e) = writeln!(
+            file,
+            "{}|{}|{}|{}",
+            ALGORITHM_VERSION, score, kb, worker_seed
+        ) {
             eprintln!("Couldn't write to file: {}", e);
         }
         print!(".");
         let _ = std::io::stdout().flush();
+
+        if !best_layouts.iter().any(|(_, other)| other.rows == kb.rows) {
+            best_layouts.push((score, kb));
+            best_layouts.sort_by_key(|(score, _)| -score);
+            best_layouts.truncate(SUMMARY_LAYOUTS);
+        }
+
+        improved
+    };
+
+    let save = |best_layouts: &[(i64, Keyboard)]| {
+        let Some(path) = &checkpoint_path else {
+            return;
+        };
+        let checkpoint = Checkpoint {
+            optimiser: options.optimiser,
+            threads: options.threads,
+            seed: options.seed,
+            resumes,
+            template: options.template.clone(),
+            elapsed: before + started.elapsed(),
+            evaluations: progress.evaluations(),
+            best: best_layouts.to_vec(),
+            state: progress.state(),
+        };
+        if let Err(e) = checkpoint::save_checkpoint(path, &checkpoint, &scorer) {
+            eprintln!("{}", e);
+        }
+    };
+
+    // Take the results from each worker in turn, so a seeded run always reports
+    // them in the same order. Waiting is cut short now and then to check the budget.
+    let mut next = 0;
+    while !progress.stopping() {
+        match results[next].recv_timeout(BUDGET_CHECK_INTERVAL) {
+            Ok(found) => {
+                if record(found, &mut best_layouts) {
+                    last_improvement = Instant::now();
+                }
+                next = (next + 1) % results.len();
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            // A worker that's finished has nothing more to give, so carry on
+            // with the rest
+            Err(RecvTimeoutError::Disconnected) => {
+                results.remove(next);
+                if results.is_empty() {
+                    break;
+                }
+                next %= results.len();
+            }
+        }
+
+        if last_checkpoint.elapsed() >= CHECKPOINT_INTERVAL {
+            save(&best_layouts);
+            last_checkpoint = Instant::now();
+        }
+
+        if let Some(reason) = options.budget.exhausted(
+            before + started.elapsed(),
+            progress.evaluations(),
+            last_improvement.elapsed(),
+        ) {
+            println!();
+            println!(
+                "{}, stopping once the workers finish their current search",
+                reason
+            );
+            progress.stop();
+        }
+    }
+
+    // Keep whatever the workers find while finishing up
+    for worker in &results {
+        for found in worker.iter() {
+            record(found, &mut best_layouts);
+        }
+    }
+    save(&best_layouts);
+
+    println!();
+    println!(
+        "Searched for {:.1}s, scoring {} layouts and swaps",
+        (before + started.elapsed()).as_secs_f64(),
+        progress.evaluations()
+    );
+    println!("Best layouts found:");
+    for (score, kb) in &best_layouts {
+        println!("{}|{}|{}", ALGORITHM_VERSION, score, kb);
+        println!("    {}", scorer.trigram_stats(kb));
+        println!("    {}", scorer.finger_load(kb));
+        print!("{}", scorer.score_breakdown(kb));
+    }
+    if let Some(path) = &checkpoint_path {
+        println!("Saved the search to {}", path.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+    use super::*;
+    use crate::optimise::random_swap_positions;
+
+    // Random n-grams of distinct letters, as counted from a corpus
+    fn random_ngrams(size: usize, rng: &mut StdRng) -> Vec<(Vec<u8>, i64)> {
+        let letters: Vec<u8> = (b'a'..=b'z').collect();
+
+        (0..200)
+            .map(|_| {
+                let ngram: Vec<u8> = letters.choose_multiple(rng, size).copied().collect();
+                (ngram, rng.gen_range(1..1000))
+            })
+            .unique_by(|(ngram, _)| ngram.clone())
+            .collect()
+    }
+
+    fn check_swap_deltas(scorer: &Scorer, rng: &mut StdRng) {
+        let mut current = scorer.score_cached(Keyboard::random_layout(rng));
+
+        for _ in 0..1000 {
+            let (from, to) = random_swap_positions(&current.keyboard, rng);
+            let delta = scorer.swap_delta(&current, from, to);
+
+            let mut swapped = current.keyboard.clone();
+            swapped.swap(from, to);
+            assert_eq!(current.score + delta, scorer.score_keyboard(&swapped));
+
+            scorer.apply_swap(&mut current, from, to);
+            assert_eq!(current.score, scorer.score_keyboard(&current.keyboard));
+        }
+    }
+
+    // Where each of the keys sits on qwerty, as (row, col)
+    fn qwerty_coords(keys: &str) -> Vec<(usize, usize)> {
+        let positions = Keyboard::qwerty().positions();
+        keys.bytes()
+            .map(|key| position_coords(positions[key as usize]))
+            .collect()
+    }
+
+    #[test]
+    fn lateral_stretches() {
+        // Middle finger to the index finger in the center column
+        assert_eq!(
+            score_lateral_stretch(&qwerty_coords("et")),
+            LATERAL_STRETCH_PENALTY
+        );
+        assert_eq!(
+            score_lateral_stretch(&qwerty_coords("hk")),
+            LATERAL_STRETCH_PENALTY
+        );
+        // Neighbouring fingers in neighbouring columns, or not neighbours at all
+        assert_eq!(score_lateral_stretch(&qwerty_coords("er")), 0);
+        assert_eq!(score_lateral_stretch(&qwerty_coords("at")), 0);
+        // The same finger, and the other hand
+        assert_eq!(score_lateral_stretch(&qwerty_coords("rt")), 0);
+        assert_eq!(score_lateral_stretch(&qwerty_coords("eh")), 0);
+    }
+
+    #[test]
+    fn scissors() {
+        // Neighbouring fingers two rows apart
+        assert_eq!(score_scissors(&qwerty_coords("ev")), FULL_SCISSOR_PENALTY);
+        assert_eq!(score_scissors(&qwerty_coords("xq")), FULL_SCISSOR_PENALTY);
+        // One row apart with the shorter finger up
+        assert_eq!(score_scissors(&qwerty_coords("wd")), HALF_SCISSOR_PENALTY);
+        assert_eq!(score_scissors(&qwerty_coords("sq")), HALF_SCISSOR_PENALTY);
+        // One row apart with the longer finger up is comfortable
+        assert_eq!(score_scissors(&qwerty_coords("es")), 0);
+        assert_eq!(score_scissors(&qwerty_coords("il")), 0);
+        // The same finger, fingers further apart, and the same row
+        assert_eq!(score_scissors(&qwerty_coords("ec")), 0);
+        assert_eq!(score_scissors(&qwerty_coords("qc")), 0);
+        assert_eq!(score_scissors(&qwerty_coords("sd")), 0);
+    }
+
+    fn qwerty_class(keys: &str) -> TrigramClass {
+        let coords = qwerty_coords(keys);
+        TrigramClass::classify((coords[0].1, coords[1].1, coords[2].1))
+    }
+
+    #[test]
+    fn trigram_classes() {
+        let cases = [
+            ("the", TrigramClass::Alternate),
+            ("aka", TrigramClass::Alternate),
+            ("asl", TrigramClass::RollIn),
+            ("lsd", TrigramClass::RollIn),
+            ("sal", TrigramClass::RollOut),
+            ("jks", TrigramClass::RollOut),
+            ("asd", TrigramClass::OneHand),
+            ("lkj", TrigramClass::OneHand),
+            ("sfd", TrigramClass::Redirect),
+            ("kjl", TrigramClass::Redirect),
+            ("sda", TrigramClass::BadRedirect),
+            ("sad", TrigramClass::BadRedirect),
+            ("ded", TrigramClass::Other),
+            ("rfk", TrigramClass::Other),
+        ];
+
+        for (keys, class) in cases {
+            assert!(
+                qwerty_class(keys) == class,
+                "{} should be {} but is {}",
+                keys,
+                class.name(),
+                qwerty_class(keys).name()
+            );
+        }
+    }
+
+    #[test]
+    fn trigram_stats_are_weighted_by_count() {
+        let scorer = Scorer::new(
+            vec![],
+            vec![],
+            vec![(b"the".to_vec(), 3), (b"asd".to_vec(), 1)],
+            vec![],
+        );
+        let stats = scorer.trigram_stats(&Keyboard::qwerty());
+
+        for (class, percentage) in stats.percentages {
+            let expected = match class {
+                TrigramClass::Alternate => 75.0,
+                TrigramClass::OneHand => 25.0,
+                _ => 0.0,
+            };
+            assert_eq!(percentage, expected, "{}", class.name());
+        }
+    }
+
+    #[test]
+    fn swap_delta_matches_rescoring_for_each_table_size() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for size in 1..=4 {
+            let ngrams = random_ngrams(size, &mut rng);
+            let single_byte = match size {
+                1 => ngrams
+                    .iter()
+                    .map(|(ngram, count)| (ngram[0], *count))
+                    .collect(),
+                _ => vec![],
+            };
+            let of_size = |n: usize| if n == size { ngrams.clone() } else { vec![] };
+
+            let scorer = Scorer::new(single_byte, of_size(2), of_size(3), of_size(4));
+            check_swap_deltas(&scorer, &mut rng);
+        }
+    }
+
+    #[test]
+    fn swap_delta_matches_rescoring_for_all_tables() {
+        let mut rng = StdRng::seed_from_u64(1);
+
+        let single_byte = random_ngrams(1, &mut rng)
+            .into_iter()
+            .map(|(ngram, count)| (ngram[0], count))
+            .collect();
+        let scorer = Scorer::new(
+            single_byte,
+            random_ngrams(2, &mut rng),
+            random_ngrams(3, &mut rng),
+            random_ngrams(4, &mut rng),
+        );
+        check_swap_deltas(&scorer, &mut rng);
+    }
+
+    #[test]
+    fn finger_load_penalty_counts_deviation_from_target() {
+        let on_target = [8, 11, 15, 16, 16, 15, 11, 8];
+        assert_eq!(finger_load_penalty(&on_target, 100), 0);
+        assert_eq!(
+            finger_load_penalty(&on_target.map(|count| count * 3), 300),
+            0
+        );
+
+        // Two presses moved from the left pinkie to the right pinkie
+        let shifted = [6, 11, 15, 16, 16, 15, 11, 10];
+        assert_eq!(
+            finger_load_penalty(&shifted, 100),
+            FINGER_LOAD_PENALTY * 4 + HAND_LOAD_PENALTY * 2 * 2
+        );
+    }
+
+    #[test]
+    fn finger_load_on_qwerty() {
+        let scorer = Scorer::new(vec![(b'a', 3), (b'j', 1)], vec![], vec![], vec![]);
+        let load = scorer.finger_load(&Keyboard::qwerty());
+
+        assert_eq!(load.fingers, [75.0, 0.0, 0.0, 0.0, 25.0, 0.0, 0.0, 0.0]);
+        assert_eq!(load.hands, [75.0, 25.0]);
+    }
+
+    #[test]
+    fn breakdown_adds_up_to_the_score() {
+        let mut rng = StdRng::seed_from_u64(2);
+
+        let single_byte = random_ngrams(1, &mut rng)
+            .into_iter()
+            .map(|(ngram, count)| (ngram[0], count))
+            .collect();
+        let scorer = Scorer::new(
+            single_byte,
+            random_ngrams(2, &mut rng),
+            random_ngrams(3, &mut rng),
+            random_ngrams(4, &mut rng),
+        );
+
+        for _ in 0..20 {
+            let kb = Keyboard::random_layout(&mut rng);
+            let breakdown = scorer.score_breakdown(&kb);
+
+            assert_eq!(breakdown.total, scorer.score_keyboard(&kb));
+            assert_eq!(
+                breakdown
+                    .metrics
+                    .iter()
+                    .map(|(_, score)| score)
+                    .sum::<i64>(),
+                breakdown.total
+            );
+        }
     }
 }
@@ -0,0 +1,133 @@
+// Simulated annealing: take any swap that improves the score, and swaps that
+// make it worse with a chance that shrinks as the temperature cools, so the
+// search can climb out of shallow local optima early on
+//
+// The schedule is set by the ANNEAL_* constants at the top of main.rs, and
+// USE_LINEAR_COOLING.
+
+use std::fmt::Display;
+
+use rand::{rngs::StdRng, Rng};
+
+use crate::{
+    optimise::{random_swap_positions, Found},
+    Keyboard, Scorer, ANNEAL_COOLING_FACTOR, ANNEAL_COOLING_STEP, ANNEAL_FINAL_TEMPERATURE,
+    ANNEAL_INITIAL_TEMPERATURE, ANNEAL_MAX_REHEATS, ANNEAL_REHEAT_AFTER, ANNEAL_REHEAT_FACTOR,
+    ANNEAL_STEPS_PER_TEMPERATURE, USE_LINEAR_COOLING,
+};
+
+// How many swaps are sampled to find the typical cost of a bad swap
+const CALIBRATION_SAMPLES: usize = 1000;
+
+// The temperature after the next step of the cooling schedule
+fn cool(temperature: f64) -> f64 {
+    if USE_LINEAR_COOLING {
+        temperature - ANNEAL_COOLING_STEP * ANNEAL_INITIAL_TEMPERATURE
+    } else {
+        temperature * ANNEAL_COOLING_FACTOR
+    }
+}
+
+#[derive(Default)]
+struct AnnealStats {
+    improved: usize,
+    // Swaps that leave the score as it was, which are always taken
+    neutral: usize,
+    accepted_worse: usize,
+    rejected: usize,
+    reheats: usize,
+}
+
+impl Display for AnnealStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let total =
+            (self.improved + self.neutral + self.accepted_worse + self.rejected).max(1) as f64;
+        let percentage = |count: usize| count as f64 * 100.0 / total;
+
+        write!(
+            f,
+            "swaps: {}  improved: {:.2}%  neutral: {:.2}%  accepted worse: {:.2}%  rejected: {:.2}%  reheats: {}",
+            total,
+            percentage(self.improved),
+            percentage(self.neutral),
+            percentage(self.accepted_worse),
+            percentage(self.rejected),
+            self.reheats
+        )
+    }
+}
+
+// The median cost of the swaps that make the layout worse. The median rather
+// than the mean, as a few swaps are hugely penalised.
+pub fn typical_worsening(scorer: &Scorer, template: &Keyboard, rng: &mut impl Rng) -> f64 {
+    let current = scorer.score_cached(template.shuffled(rng));
+
+    let mut costs: Vec<i64> = (0..CALIBRATION_SAMPLES)
+        .map(|_| {
+            let (from, to) = random_swap_positions(&current.keyboard, rng);
+            -scorer.swap_delta(&current, from, to)
+        })
+        .filter(|cost| *cost > 0)
+        .collect();
+    costs.sort();
+
+    costs.get(costs.len() / 2).map_or(1.0, |cost| *cost as f64)
+}
+
+pub fn anneal(scorer: &Scorer, template: &Keyboard, rng: &mut StdRng) -> Found {
+    let scale = typical_worsening(scorer, template, rng);
+
+    let mut current = scorer.score_cached(template.shuffled(rng));
+    let mut best = (current.score, current.keyboard.clone());
+
+    let mut stats = AnnealStats::default();
+    let mut temperature = ANNEAL_INITIAL_TEMPERATURE;
+    let mut since_best = 0;
+
+    loop {
+        for _ in 0..ANNEAL_STEPS_PER_TEMPERATURE {
+            let (from, to) = random_swap_positions(&current.keyboard, rng);
+            let delta = scorer.swap_delta(&current, from, to);
+
+            let accept = if delta > 0 {
+                stats.improved += 1;
+                true
+            } else if delta == 0 {
+                stats.neutral += 1;
+                true
+            } else if rng.gen::<f64>() < (delta as f64 / (temperature * scale)).exp() {
+                stats.accepted_worse += 1;
+                true
+            } else {
+                stats.rejected += 1;
+                false
+            };
+
+            if accept {
+                scorer.apply_swap(&mut current, from, to);
+
+                if current.score > best.0 {
+                    best = (current.score, current.keyboard.clone());
+                    since_best = 0;
+                }
+            }
+        }
+
+        since_best += 1;
+        temperature = cool(temperature);
+
+        if since_best >= ANNEAL_REHEAT_AFTER && stats.reheats < ANNEAL_MAX_REHEATS {
+            temperature = ANNEAL_INITIAL_TEMPERATURE * ANNEAL_REHEAT_FACTOR;
+            stats.reheats += 1;
+            since_best = 0;
+        } else if temperature < ANNEAL_FINAL_TEMPERATURE {
+            break;
+        }
+    }
+
+    Found {
+        score: best.0,
+        keyboard: best.1,
+        stats: Some(stats.to_string()),
+    }
+}
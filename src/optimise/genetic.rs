@@ -0,0 +1,258 @@
+// A genetic algorithm: keep a population of layouts, breed new ones by crossing
+// over the key order of two parents picked by tournament, mutate them with
+// random swaps, and carry the best few over to each generation unchanged
+//
+// The population and rates are set by the GENETIC_* constants at the top of
+// main.rs, and the kind of crossover by USE_PMX_CROSSOVER.
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng};
+
+use crate::{
+    optimise::{random_swap_positions, Found},
+    Keyboard, Scorer, GENETIC_ELITES, GENETIC_MUTATION_RATE, GENETIC_POPULATION,
+    GENETIC_STALL_LIMIT, GENETIC_TOURNAMENT_SIZE, USE_PMX_CROSSOVER,
+};
+
+#[derive(Clone)]
+struct Individual {
+    score: i64,
+    keyboard: Keyboard,
+}
+
+// The filler keys appear more than once, so number them to make the keys of a
+// layout a true permutation. Keys are all printable, so the numbers can't clash.
+fn to_genes(kb: &Keyboard) -> Vec<u8> {
+    let mut fillers = 0;
+    kb.rows
+        .iter()
+        .flatten()
+        .map(|key| {
+            if *key == b'_' {
+                fillers += 1;
+                fillers
+            } else {
+                *key
+            }
+        })
+        .collect()
+}
+
+fn from_genes(template: &Keyboard, genes: &[u8]) -> Keyboard {
+    let keys: Vec<u8> = genes
+        .iter()
+        .map(|gene| if gene.is_ascii_graphic() { *gene } else { b'_' })
+        .collect();
+
+    Keyboard {
+        rows: keys.chunks(10).map(|row| row.to_vec()).collect(),
+        ..template.clone()
+    }
+}
+
+// Breeds the keys of a child from the keys of two parents, keeping the run of
+// the first parent's keys between the two points
+type Crossover = fn(&[u8], &[u8], usize, usize) -> Vec<u8>;
+
+// Order crossover: copy a run of keys from the first parent, and fill in the
+// rest in the order they come in the second
+fn order_crossover(first: &[u8], second: &[u8], start: usize, end: usize) -> Vec<u8> {
+    let len = first.len();
+    let mut child = vec![None; len];
+    for idx in start..end {
+        child[idx] = Some(first[idx]);
+    }
+
+    let mut rest = (0..len)
+        .map(|idx| second[(end + idx) % len])
+        .filter(|gene| !first[start..end].contains(gene));
+    for idx in (0..len).map(|idx| (end + idx) % len) {
+        if child[idx].is_none() {
+            child[idx] = rest.next();
+        }
+    }
+
+    child.into_iter().flatten().collect()
+}
+
+// Partially mapped crossover: copy a run of keys from the first parent, and
+// keep the rest where the second parent has them when possible
+fn pmx_crossover(first: &[u8], second: &[u8], start: usize, end: usize) -> Vec<u8> {
+    let mut child = vec![None; first.len()];
+    for idx in start..end {
+        child[idx] = Some(first[idx]);
+    }
+
+    // Each key of the second parent's run that the first parent's run displaced
+    // goes where the key it's mapped to sits in the second parent
+    for idx in start..end {
+        let gene = second[idx];
+        if first[start..end].contains(&gene) {
+            continue;
+        }
+
+        let mut position = idx;
+        while (start..end).contains(&position) {
+            let mapped = first[position];
+            position = second.iter().position(|g| *g == mapped).unwrap_or(position);
+        }
+        child[position] = Some(gene);
+    }
+
+    for (idx, gene) in child.iter_mut().enumerate() {
+        if gene.is_none() {
+            *gene = Some(second[idx]);
+        }
+    }
+
+    child.into_iter().flatten().collect()
+}
+
+// Cross over the keys that aren't pinned, which both parents have in the same places
+fn crossover(
+    first: &Keyboard,
+    second: &Keyboard,
+    cross: Crossover,
+    rng: &mut impl Rng,
+) -> Keyboard {
+    let (first_genes, second_genes) = (to_genes(first), to_genes(second));
+
+    let free: Vec<usize> = (0..first_genes.len())
+        .filter(|idx| !first.pinned.contains(&first_genes[*idx]))
+        .collect();
+    let first_free: Vec<u8> = free.iter().map(|idx| first_genes[*idx]).collect();
+    let second_free: Vec<u8> = free.iter().map(|idx| second_genes[*idx]).collect();
+
+    let mut bounds = [rng.gen_range(0..=free.len()), rng.gen_range(0..=free.len())];
+    bounds.sort();
+
+    let child_free = cross(&first_free, &second_free, bounds[0], bounds[1]);
+
+    let mut child = first_genes;
+    for (idx, gene) in free.iter().zip(child_free) {
+        child[*idx] = gene;
+    }
+
+    from_genes(first, &child)
+}
+
+// The best of a few individuals picked at random
+fn tournament<'a>(population: &'a [Individual], rng: &mut impl Rng) -> &'a Individual {
+    (0..GENETIC_TOURNAMENT_SIZE)
+        .filter_map(|_| population.choose(rng))
+        .max_by_key(|individual| individual.score)
+        .unwrap_or(&population[0])
+}
+
+pub fn genetic(scorer: &Scorer, template: &Keyboard, rng: &mut StdRng) -> Found {
+    let mut population: Vec<Individual> = (0..GENETIC_POPULATION)
+        .map(|_| {
+            let keyboard = template.shuffled(rng);
+            Individual {
+                score: scorer.score_keyboard(&keyboard),
+                keyboard,
+            }
+        })
+        .collect();
+
+    let cross: Crossover = if USE_PMX_CROSSOVER {
+        pmx_crossover
+    } else {
+        order_crossover
+    };
+    let mut generations = 0;
+    let mut since_improvement = 0;
+    let mut best = i64::MIN;
+
+    while since_improvement < GENETIC_STALL_LIMIT {
+        population.sort_by_key(|individual| -individual.score);
+
+        if population[0].score > best {
+            best = population[0].score;
+            since_improvement = 0;
+        } else {
+            since_improvement += 1;
+        }
+
+        let mut next: Vec<Individual> = population[..GENETIC_ELITES].to_vec();
+
+        while next.len() < GENETIC_POPULATION {
+            let first = tournament(&population, rng);
+            let second = tournament(&population, rng);
+            let mut keyboard = crossover(&first.keyboard, &second.keyboard, cross, rng);
+
+            while rng.gen::<f64>() < GENETIC_MUTATION_RATE {
+                let (from, to) = random_swap_positions(&keyboard, rng);
+                keyboard.swap(from, to);
+            }
+
+            next.push(Individual {
+                score: scorer.score_keyboard(&keyboard),
+                keyboard,
+            });
+        }
+
+        population = next;
+        generations += 1;
+    }
+
+    // The last generation hasn't been sorted, so look for its best
+    let best = population
+        .into_iter()
+        .max_by_key(|individual| individual.score)
+        .unwrap();
+
+    Found {
+        score: best.score,
+        keyboard: best.keyboard,
+        stats: Some(format!("generations: {}", generations)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn sorted_keys(kb: &Keyboard) -> Vec<u8> {
+        let mut keys: Vec<u8> = kb.rows.iter().flatten().copied().collect();
+        keys.sort();
+        keys
+    }
+
+    // Children hold the same keys as their parents, with the pinned ones where
+    // the parents have them
+    fn check_crossover(cross: Crossover) {
+        let mut rng = StdRng::seed_from_u64(44);
+
+        for _ in 0..2000 {
+            let mut template = Keyboard::random_layout(&mut rng);
+            let letters: Vec<u8> = (b'a'..=b'z').collect();
+            let pinned = rng.gen_range(0..=10);
+            template.pinned = letters.choose_multiple(&mut rng, pinned).copied().collect();
+
+            let first = template.shuffled(&mut rng);
+            let second = template.shuffled(&mut rng);
+            let child = crossover(&first, &second, cross, &mut rng);
+
+            assert_eq!(sorted_keys(&child), sorted_keys(&template), "{}", child);
+            let (positions, template_positions) = (child.positions(), template.positions());
+            for key in &template.pinned {
+                assert_eq!(
+                    positions[*key as usize], template_positions[*key as usize],
+                    "'{}' moved in {}",
+                    *key as char, child
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn order_crossover_keeps_keys() {
+        check_crossover(order_crossover);
+    }
+
+    #[test]
+    fn pmx_crossover_keeps_keys() {
+        check_crossover(pmx_crossover);
+    }
+}
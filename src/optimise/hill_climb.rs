@@ -0,0 +1,35 @@
+// Greedy hill climbing: only take swaps that improve the score, and stop once
+// none have been found for a while
+
+use rand::rngs::StdRng;
+
+use crate::{
+    optimise::{random_swap_positions, Found},
+    Keyboard, Scorer, FAILED_TO_IMPROVE_LIMIT,
+};
+
+pub fn hill_climb(scorer: &Scorer, template: &Keyboard, rng: &mut StdRng) -> Found {
+    let mut current = scorer.score_cached(template.shuffled(rng));
+    let mut time_since_last_improvement = 0;
+
+    loop {
+        time_since_last_improvement += 1;
+
+        let (from, to) = random_swap_positions(&current.keyboard, rng);
+
+        if scorer.swap_delta(&current, from, to) > 0 {
+            scorer.apply_swap(&mut current, from, to);
+            time_since_last_improvement = 0;
+        }
+
+        if time_since_last_improvement >= FAILED_TO_IMPROVE_LIMIT {
+            break;
+        }
+    }
+
+    Found {
+        score: current.score,
+        keyboard: current.keyboard,
+        stats: None,
+    }
+}
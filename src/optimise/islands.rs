@@ -0,0 +1,175 @@
+// The island model: each thread searches on its own island, climbing from its
+// layout and kicking it with a few random swaps whenever it gets stuck. Every
+// ISLAND_MIGRATION_INTERVAL swaps the islands meet and add their best layouts to
+// a pool shared by all islands, holding the best ISLAND_POOL_SIZE layouts found
+// anywhere, then each takes a random layout from the pool in place of its own if
+// that's better. With a pool size of 1, islands always take the best layout
+// found so far.
+
+use std::{
+    sync::{
+        mpsc::{channel, Receiver},
+        Arc, Barrier, Mutex,
+    },
+    thread,
+};
+
+use rand::seq::SliceRandom;
+
+use crate::{
+    optimise::{polished, random_swap_positions, worker_rng, Found, Progress},
+    Keyboard, Scorer, FAILED_TO_IMPROVE_LIMIT, ISLAND_KICK_SWAPS, ISLAND_MIGRATION_INTERVAL,
+    ISLAND_POOL_SIZE,
+};
+
+// The best layouts found by any island, best first
+type Pool = Vec<(i64, Keyboard)>;
+
+// What the islands share between migrations
+struct Migration {
+    // The best layout of each island, offered to the pool
+    offers: Vec<(i64, Keyboard)>,
+    pool: Pool,
+    taken: usize,
+    reported: i64,
+    // Decided once per migration, so every island stops after the same one
+    stopping: bool,
+    // Set when a result couldn't be sent, as nothing's listening any more
+    disconnected: bool,
+}
+
+fn add_to_pool(pool: &mut Pool, score: i64, keyboard: &Keyboard) {
+    if pool.iter().any(|(_, kb)| kb.rows == keyboard.rows) {
+        return;
+    }
+
+    pool.push((score, keyboard.clone()));
+    pool.sort_by_key(|(score, _)| -score);
+    pool.truncate(ISLAND_POOL_SIZE);
+}
+
+pub fn start(
+    scorer: &Scorer,
+    template: &Keyboard,
+    islands: usize,
+    seed: u64,
+    progress: Arc<Progress>,
+) -> Receiver<Found> {
+    let (sender, receiver) = channel();
+
+    let migration = Arc::new(Mutex::new(Migration {
+        offers: vec![(i64::MIN, template.clone()); islands],
+        pool: vec![],
+        taken: 0,
+        reported: i64::MIN,
+        stopping: false,
+        disconnected: false,
+    }));
+    let barrier = Arc::new(Barrier::new(islands));
+    let resumed = progress.state();
+
+    for island in 0..islands {
+        let scorer = scorer.clone();
+        let template = template.clone();
+        let sender = sender.clone();
+        let migration = migration.clone();
+        let barrier = barrier.clone();
+        let progress = progress.clone();
+        let resumed = resumed.get(island).cloned();
+
+        thread::spawn(move || {
+            let mut rng = worker_rng(seed, island);
+
+            // Start from a shuffle, unless resuming from the island's best
+            let start = resumed.unwrap_or_else(|| template.shuffled(&mut rng));
+            let mut current = scorer.score_cached(start);
+            let mut best = (current.score, current.keyboard.clone());
+            let mut since_improvement = 0;
+
+            loop {
+                for _ in 0..ISLAND_MIGRATION_INTERVAL {
+                    let (from, to) = random_swap_positions(&current.keyboard, &mut rng);
+
+                    if scorer.swap_delta(&current, from, to) > 0 {
+                        scorer.apply_swap(&mut current, from, to);
+                        since_improvement = 0;
+
+                        if current.score > best.0 {
+                            best = (current.score, current.keyboard.clone());
+                        }
+                    } else {
+                        since_improvement += 1;
+                    }
+
+                    // Stuck, so kick the layout somewhere nearby to climb again
+                    if since_improvement >= FAILED_TO_IMPROVE_LIMIT {
+                        for _ in 0..ISLAND_KICK_SWAPS {
+                            let (from, to) = random_swap_positions(&current.keyboard, &mut rng);
+                            scorer.apply_swap(&mut current, from, to);
+                        }
+                        since_improvement = 0;
+                    }
+                }
+
+                migration.lock().unwrap().offers[island] = best.clone();
+
+                // One island adds every offer to the pool, in the same order each
+                // time, while the others wait for it
+                let mut found = None;
+                if barrier.wait().is_leader() {
+                    let mut migration = migration.lock().unwrap();
+                    let Migration { offers, pool, .. } = &mut *migration;
+                    for (score, keyboard) in offers.iter() {
+                        add_to_pool(pool, *score, keyboard);
+                    }
+                    migration.stopping = progress.stopping() || migration.disconnected;
+                    progress.set_state(
+                        migration
+                            .offers
+                            .iter()
+                            .map(|(_, keyboard)| keyboard.clone())
+                            .collect(),
+                    );
+
+                    let (score, keyboard) = migration.pool[0].clone();
+                    if score > migration.reported {
+                        migration.reported = score;
+                        let stats = format!("migrations taken: {}", migration.taken);
+
+                        found = Some(Found {
+                            score,
+                            keyboard,
+                            stats: Some(stats),
+                        });
+                    }
+                }
+                barrier.wait();
+                progress.report_evaluations();
+
+                let mut shared = migration.lock().unwrap();
+                let stopping = shared.stopping;
+                if !stopping {
+                    if let Some((score, keyboard)) = shared.pool.choose(&mut rng).cloned() {
+                        if score > current.score {
+                            current = scorer.score_cached(keyboard);
+                            shared.taken += 1;
+                        }
+                    }
+                }
+                drop(shared);
+
+                // Polished once the other islands are on their way again
+                if let Some(found) = found {
+                    if sender.send(polished(&scorer, found)).is_err() {
+                        migration.lock().unwrap().disconnected = true;
+                    }
+                }
+                if stopping {
+                    return;
+                }
+            }
+        });
+    }
+
+    receiver
+}
@@ -0,0 +1,295 @@
+// The ways of searching for a good layout. Most optimisers run independent
+// searches on each worker thread, where each run starts from a shuffle of the
+// template and returns the best layout it found. Parallel tempering instead runs
+// one chain per thread, and the chains trade layouts as they go, and the island
+// model shares the best layouts between threads through a pool. Results are
+// polished with steepest ascent when USE_POLISH is set.
+//
+// Workers stop once they've finished the search they're on after being told
+// to stop through the shared Progress. Parallel tempering and the island model
+// also keep the layouts they'd carry on from there, to checkpoint the search.
+
+pub mod anneal;
+pub mod genetic;
+mod hill_climb;
+mod islands;
+mod steepest;
+mod tabu;
+mod tempering;
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{channel, Receiver},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use iter_tools::prelude::*;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::{take_evaluations, Keyboard, Scorer, USE_POLISH};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Optimiser {
+    HillClimb,
+    Anneal,
+    Steepest,
+    Genetic,
+    Tabu,
+    Tempering,
+    Islands,
+}
+
+impl Optimiser {
+    pub const ALL: [Optimiser; 7] = [
+        Optimiser::HillClimb,
+        Optimiser::Anneal,
+        Optimiser::Steepest,
+        Optimiser::Genetic,
+        Optimiser::Tabu,
+        Optimiser::Tempering,
+        Optimiser::Islands,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Optimiser::HillClimb => "hill-climb",
+            Optimiser::Anneal => "anneal",
+            Optimiser::Steepest => "steepest",
+            Optimiser::Genetic => "genetic",
+            Optimiser::Tabu => "tabu",
+            Optimiser::Tempering => "tempering",
+            Optimiser::Islands => "islands",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Optimiser> {
+        Optimiser::ALL
+            .iter()
+            .find(|optimiser| optimiser.name() == name)
+            .copied()
+    }
+
+    // Whether it saves the layouts it's searching from in a checkpoint. The
+    // others start afresh when resumed, with only the best layouts kept.
+    pub fn saves_state(&self) -> bool {
+        matches!(self, Optimiser::Tempering | Optimiser::Islands)
+    }
+
+    // Start searching on the given number of threads. Each receiver gives the
+    // layouts found by one worker, always the same ones for the same seed.
+    pub fn start(
+        &self,
+        scorer: &Scorer,
+        template: &Keyboard,
+        threads: usize,
+        seed: u64,
+        progress: Arc<Progress>,
+    ) -> Vec<Receiver<Found>> {
+        match self {
+            Optimiser::Tempering => {
+                return vec![tempering::start(scorer, template, threads, seed, progress)]
+            }
+            Optimiser::Islands => {
+                return vec![islands::start(scorer, template, threads, seed, progress)]
+            }
+            _ => {}
+        }
+
+        (0..threads)
+            .map(|worker| {
+                let (sender, receiver) = channel();
+                let scorer = scorer.clone();
+                let template = template.clone();
+                let optimiser = *self;
+                let progress = progress.clone();
+
+                thread::spawn(move || {
+                    let mut rng = worker_rng(seed, worker);
+
+                    while !progress.stopping() {
+                        let found = optimiser.run(&scorer, &template, &mut rng);
+                        progress.report_evaluations();
+
+                        if sender.send(found).is_err() {
+                            break;
+                        }
+                    }
+                });
+
+                receiver
+            })
+            .collect()
+    }
+
+    fn run(&self, scorer: &Scorer, template: &Keyboard, rng: &mut StdRng) -> Found {
+        let found = match self {
+            Optimiser::HillClimb => hill_climb::hill_climb(scorer, template, rng),
+            Optimiser::Anneal => anneal::anneal(scorer, template, rng),
+            Optimiser::Steepest => steepest::steepest(scorer, template, rng),
+            Optimiser::Genetic => genetic::genetic(scorer, template, rng),
+            Optimiser::Tabu => tabu::tabu(scorer, template, rng),
+            Optimiser::Tempering | Optimiser::Islands => {
+                unreachable!("{} runs its threads together", self.name())
+            }
+        };
+
+        // Steepest ascent already stops where polishing would
+        if *self == Optimiser::Steepest {
+            return found;
+        }
+
+        polished(scorer, found)
+    }
+}
+
+fn polished(scorer: &Scorer, found: Found) -> Found {
+    if !USE_POLISH {
+        return found;
+    }
+
+    let polished = steepest::polish(scorer, scorer.score_cached(found.keyboard));
+    Found {
+        score: polished.score,
+        keyboard: polished.keyboard,
+        ..found
+    }
+}
+
+// Shared by main and the workers: how much searching has been done, and
+// whether it's time to stop
+#[derive(Default)]
+pub struct Progress {
+    evaluations: AtomicU64,
+    stopping: AtomicBool,
+    // The layouts to carry on searching from, for the optimisers that run their
+    // threads together: a chain's layout or an island's best, one per thread
+    state: Mutex<Vec<Keyboard>>,
+}
+
+impl Progress {
+    // Carry on from a checkpoint
+    pub fn resuming(evaluations: u64, state: Vec<Keyboard>) -> Progress {
+        Progress {
+            evaluations: AtomicU64::new(evaluations),
+            stopping: AtomicBool::new(false),
+            state: Mutex::new(state),
+        }
+    }
+
+    pub fn evaluations(&self) -> u64 {
+        self.evaluations.load(Ordering::Relaxed)
+    }
+
+    // Add the layouts and swaps scored by this thread since it last reported
+    pub fn report_evaluations(&self) {
+        self.evaluations
+            .fetch_add(take_evaluations(), Ordering::Relaxed);
+    }
+
+    pub fn stop(&self) {
+        self.stopping.store(true, Ordering::Relaxed);
+    }
+
+    pub fn stopping(&self) -> bool {
+        self.stopping.load(Ordering::Relaxed)
+    }
+
+    pub fn state(&self) -> Vec<Keyboard> {
+        self.state.lock().unwrap().clone()
+    }
+
+    pub fn set_state(&self, state: Vec<Keyboard>) {
+        *self.state.lock().unwrap() = state;
+    }
+}
+
+pub struct Found {
+    pub score: i64,
+    pub keyboard: Keyboard,
+    // How the search went, for the optimisers that keep track
+    pub stats: Option<String>,
+}
+
+// The random numbers for one worker, which only depend on the seed of the run
+// and the number of the worker
+pub fn worker_rng(seed: u64, worker: usize) -> StdRng {
+    // splitmix64, so the seeds of neighbouring workers look nothing alike
+    let mut z = seed.wrapping_add((worker as u64 + 1).wrapping_mul(0x9e3779b97f4a7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+
+    StdRng::seed_from_u64(z ^ (z >> 31))
+}
+
+// A random key that isn't pinned. Layout files always leave some to swap.
+pub fn random_position(kb: &Keyboard, rng: &mut impl Rng) -> (usize, usize) {
+    let free: Vec<_> = (0..3)
+        .cartesian_product(0..10)
+        .filter(|(row, col)| !kb.pinned.contains(&kb.rows[*row][*col]))
+        .collect();
+
+    *free.choose(rng).expect("every key of the layout is pinned")
+}
+
+pub fn random_swap_positions(
+    kb: &Keyboard,
+    rng: &mut impl Rng,
+) -> ((usize, usize), (usize, usize)) {
+    (random_position(kb, rng), random_position(kb, rng))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A scorer with few n-grams, so each search is quick
+    fn small_scorer() -> Scorer {
+        let mut rng = StdRng::seed_from_u64(1);
+        let letters: Vec<u8> = (b'a'..=b'z').collect();
+
+        let single_byte = letters
+            .iter()
+            .map(|letter| (*letter, rng.gen_range(1..1000)))
+            .collect();
+        let double_byte = (0..50)
+            .map(|_| {
+                let bigram: Vec<u8> = letters.choose_multiple(&mut rng, 2).copied().collect();
+                (bigram, rng.gen_range(1..1000))
+            })
+            .unique_by(|(bigram, _)| bigram.clone())
+            .collect();
+
+        Scorer::new(single_byte, double_byte, vec![], vec![])
+    }
+
+    // The first layout each worker sends back
+    fn first_found(optimiser: Optimiser, scorer: &Scorer, seed: u64) -> Vec<(i64, Vec<Vec<u8>>)> {
+        let progress = Arc::new(Progress::default());
+        let receivers = optimiser.start(scorer, &Keyboard::qwerty(), 2, seed, progress.clone());
+
+        let found = receivers
+            .iter()
+            .flat_map(|receiver| receiver.iter().take(1))
+            .map(|found| (found.score, found.keyboard.rows))
+            .collect();
+        progress.stop();
+
+        found
+    }
+
+    #[test]
+    fn seeded_runs_find_the_same_layouts() {
+        let scorer = small_scorer();
+
+        for optimiser in Optimiser::ALL {
+            let first = first_found(optimiser, &scorer, 7);
+            let second = first_found(optimiser, &scorer, 7);
+
+            assert!(!first.is_empty(), "{} found nothing", optimiser.name());
+            assert_eq!(first, second, "{} isn't reproducible", optimiser.name());
+        }
+    }
+}
@@ -0,0 +1,49 @@
+// Steepest ascent: try every swap of two keys, take the best one, and repeat
+// until no swap improves the score. What's left is a layout that no single swap
+// can improve, so this also polishes the results of the other optimisers.
+
+use rand::rngs::StdRng;
+
+use crate::{optimise::Found, Keyboard, ScoredKeyboard, Scorer};
+
+// Every pair of positions whose keys can be swapped
+pub fn swappable_pairs(kb: &Keyboard) -> Vec<((usize, usize), (usize, usize))> {
+    let positions: Vec<(usize, usize)> = (0..3)
+        .flat_map(|row| (0..10).map(move |col| (row, col)))
+        .filter(|(row, col)| !kb.pinned.contains(&kb.rows[*row][*col]))
+        .collect();
+
+    let mut pairs = vec![];
+    for (idx, from) in positions.iter().enumerate() {
+        for to in &positions[idx + 1..] {
+            pairs.push((*from, *to));
+        }
+    }
+    pairs
+}
+
+pub fn polish(scorer: &Scorer, mut current: ScoredKeyboard) -> ScoredKeyboard {
+    let pairs = swappable_pairs(&current.keyboard);
+
+    loop {
+        let best = pairs
+            .iter()
+            .map(|(from, to)| (scorer.swap_delta(&current, *from, *to), *from, *to))
+            .max_by_key(|(delta, _, _)| *delta);
+
+        match best {
+            Some((delta, from, to)) if delta > 0 => scorer.apply_swap(&mut current, from, to),
+            _ => return current,
+        }
+    }
+}
+
+pub fn steepest(scorer: &Scorer, template: &Keyboard, rng: &mut StdRng) -> Found {
+    let current = polish(scorer, scorer.score_cached(template.shuffled(rng)));
+
+    Found {
+        score: current.score,
+        keyboard: current.keyboard,
+        stats: None,
+    }
+}
@@ -0,0 +1,83 @@
+// Tabu search: each step takes the best swap there is, even if it makes the
+// layout worse, but swapping a pair of keys back is forbidden for a while so the
+// search moves on rather than undoing itself. A forbidden swap is still taken if
+// it would give the best layout found so far.
+//
+// The tenure and stopping point are set by the TABU_* constants at the top of
+// main.rs.
+
+use std::collections::VecDeque;
+
+use rand::rngs::StdRng;
+
+use crate::{
+    optimise::{steepest::swappable_pairs, Found},
+    Keyboard, Scorer, TABU_STALL_LIMIT, TABU_TENURE,
+};
+
+pub fn tabu(scorer: &Scorer, template: &Keyboard, rng: &mut StdRng) -> Found {
+    let mut current = scorer.score_cached(template.shuffled(rng));
+    let mut best = (current.score, current.keyboard.clone());
+
+    let pairs = swappable_pairs(&current.keyboard);
+
+    // The most recently swapped pairs of keys, smallest key first
+    let mut tabu: VecDeque<(u8, u8)> = VecDeque::with_capacity(TABU_TENURE + 1);
+
+    let mut steps = 0;
+    let mut aspirations = 0;
+    let mut since_best = 0;
+
+    while since_best < TABU_STALL_LIMIT {
+        let mut chosen = None;
+
+        for (from, to) in &pairs {
+            let a = current.keyboard.rows[from.0][from.1];
+            let b = current.keyboard.rows[to.0][to.1];
+            if a == b {
+                continue;
+            }
+
+            let delta = scorer.swap_delta(&current, *from, *to);
+            let is_tabu = tabu.contains(&(a.min(b), a.max(b)));
+            let aspires = current.score + delta > best.0;
+
+            if (!is_tabu || aspires)
+                && chosen.is_none_or(|(best_delta, _, _, _)| delta > best_delta)
+            {
+                chosen = Some((delta, *from, *to, is_tabu));
+            }
+        }
+
+        let Some((_, from, to, was_tabu)) = chosen else {
+            break;
+        };
+
+        let a = current.keyboard.rows[from.0][from.1];
+        let b = current.keyboard.rows[to.0][to.1];
+        scorer.apply_swap(&mut current, from, to);
+
+        tabu.push_back((a.min(b), a.max(b)));
+        if tabu.len() > TABU_TENURE {
+            tabu.pop_front();
+        }
+
+        if was_tabu {
+            aspirations += 1;
+        }
+        steps += 1;
+
+        if current.score > best.0 {
+            best = (current.score, current.keyboard.clone());
+            since_best = 0;
+        } else {
+            since_best += 1;
+        }
+    }
+
+    Found {
+        score: best.0,
+        keyboard: best.1,
+        stats: Some(format!("steps: {}  aspirations: {}", steps, aspirations)),
+    }
+}
@@ -0,0 +1,197 @@
+// Parallel tempering: one annealing chain per thread, each held at its own
+// temperature. The hot chains roam freely while the cold ones settle into good
+// layouts, and every so often neighbouring chains offer to trade layouts, so a
+// promising layout found while hot gets carried down to be refined.
+//
+// The temperatures and exchange interval are set by the TEMPERING_* constants
+// at the top of main.rs.
+
+use std::{
+    sync::{
+        mpsc::{channel, Receiver},
+        Arc, Barrier, Mutex,
+    },
+    thread,
+};
+
+use rand::{rngs::StdRng, Rng};
+
+use crate::{
+    optimise::{
+        anneal::typical_worsening, polished, random_swap_positions, worker_rng, Found, Progress,
+    },
+    Keyboard, Scorer, TEMPERING_EXCHANGE_INTERVAL, TEMPERING_MAX_TEMPERATURE,
+    TEMPERING_MIN_TEMPERATURE,
+};
+
+// What the chains share between exchanges
+struct Exchange {
+    // The current layout of each chain, coldest first
+    layouts: Vec<(i64, Keyboard)>,
+    rounds: usize,
+    offered: usize,
+    traded: usize,
+    best: i64,
+    // For the trades, which are made by whichever chain reaches the barrier last
+    rng: StdRng,
+    // Decided once per round, so every chain stops after the same one
+    stopping: bool,
+    // Set when a result couldn't be sent, as nothing's listening any more
+    disconnected: bool,
+}
+
+fn temperatures(chains: usize) -> Vec<f64> {
+    let ratio = TEMPERING_MAX_TEMPERATURE / TEMPERING_MIN_TEMPERATURE;
+
+    (0..chains)
+        .map(|chain| {
+            let step = if chains > 1 {
+                chain as f64 / (chains - 1) as f64
+            } else {
+                0.0
+            };
+            TEMPERING_MIN_TEMPERATURE * ratio.powf(step)
+        })
+        .collect()
+}
+
+// Offer trades between neighbouring chains, alternating between the even and odd
+// pairs each round. A trade that moves a better layout to the colder chain is
+// always made, and otherwise with the usual Metropolis chance.
+fn trade(exchange: &mut Exchange, temperatures: &[f64], scale: f64) {
+    let first = exchange.rounds % 2;
+
+    for cold in (first..temperatures.len().saturating_sub(1)).step_by(2) {
+        let hot = cold + 1;
+        let gain = (exchange.layouts[hot].0 - exchange.layouts[cold].0) as f64 / scale;
+        let chance = (gain * (1.0 / temperatures[cold] - 1.0 / temperatures[hot])).exp();
+
+        exchange.offered += 1;
+        if exchange.rng.gen::<f64>() < chance {
+            exchange.layouts.swap(cold, hot);
+            exchange.traded += 1;
+        }
+    }
+
+    exchange.rounds += 1;
+}
+
+pub fn start(
+    scorer: &Scorer,
+    template: &Keyboard,
+    chains: usize,
+    seed: u64,
+    progress: Arc<Progress>,
+) -> Receiver<Found> {
+    let (sender, receiver) = channel();
+
+    let mut rng = worker_rng(seed, chains);
+    let scale = typical_worsening(scorer, template, &mut rng);
+    let temperatures = temperatures(chains);
+
+    // Every chain starts from its own shuffle, unless it's resuming
+    let mut resumed = progress.state().into_iter();
+    let layouts = (0..chains)
+        .map(|_| {
+            let keyboard = resumed
+                .next()
+                .unwrap_or_else(|| template.shuffled(&mut rng));
+            (scorer.score_keyboard(&keyboard), keyboard)
+        })
+        .collect();
+
+    let exchange = Arc::new(Mutex::new(Exchange {
+        layouts,
+        rounds: 0,
+        offered: 0,
+        traded: 0,
+        best: i64::MIN,
+        rng,
+        stopping: false,
+        disconnected: false,
+    }));
+    let barrier = Arc::new(Barrier::new(chains));
+
+    for chain in 0..chains {
+        let scorer = scorer.clone();
+        let sender = sender.clone();
+        let exchange = exchange.clone();
+        let barrier = barrier.clone();
+        let temperatures = temperatures.clone();
+        let progress = progress.clone();
+
+        thread::spawn(move || {
+            let mut rng = worker_rng(seed, chain);
+            let temperature = temperatures[chain] * scale;
+
+            loop {
+                let layout = exchange.lock().unwrap().layouts[chain].1.clone();
+                let mut current = scorer.score_cached(layout);
+
+                for _ in 0..TEMPERING_EXCHANGE_INTERVAL {
+                    let (from, to) = random_swap_positions(&current.keyboard, &mut rng);
+                    let delta = scorer.swap_delta(&current, from, to);
+
+                    if delta > 0 || rng.gen::<f64>() < (delta as f64 / temperature).exp() {
+                        scorer.apply_swap(&mut current, from, to);
+                    }
+                }
+
+                exchange.lock().unwrap().layouts[chain] = (current.score, current.keyboard);
+
+                // One chain makes the trades while the others wait for it
+                let mut found = None;
+                if barrier.wait().is_leader() {
+                    let mut exchange = exchange.lock().unwrap();
+                    trade(&mut exchange, &temperatures, scale);
+                    exchange.stopping = progress.stopping() || exchange.disconnected;
+                    progress.set_state(
+                        exchange
+                            .layouts
+                            .iter()
+                            .map(|(_, keyboard)| keyboard.clone())
+                            .collect(),
+                    );
+
+                    let best = exchange
+                        .layouts
+                        .iter()
+                        .max_by_key(|(score, _)| *score)
+                        .cloned();
+
+                    if let Some((score, keyboard)) =
+                        best.filter(|(score, _)| *score > exchange.best)
+                    {
+                        exchange.best = score;
+                        let stats = format!(
+                            "rounds: {}  trades: {:.2}%",
+                            exchange.rounds,
+                            exchange.traded as f64 * 100.0 / exchange.offered.max(1) as f64
+                        );
+
+                        found = Some(Found {
+                            score,
+                            keyboard,
+                            stats: Some(stats),
+                        });
+                    }
+                }
+                barrier.wait();
+                progress.report_evaluations();
+                let stopping = exchange.lock().unwrap().stopping;
+
+                // Polished once the other chains are on their way again
+                if let Some(found) = found {
+                    if sender.send(polished(&scorer, found)).is_err() {
+                        exchange.lock().unwrap().disconnected = true;
+                    }
+                }
+                if stopping {
+                    return;
+                }
+            }
+        });
+    }
+
+    receiver
+}
@@ -0,0 +1,237 @@
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    export::{keep_keys, Format},
+    layout,
+    optimise::Optimiser,
+    Keyboard, WORKER_THREADS,
+};
+
+pub struct Options {
+    pub corpus: Vec<String>,
+    pub debug: bool,
+    pub breakdown: bool,
+    // Draw each layout as a heatmap, optionally with finger load and same finger bigrams
+    pub heatmap: bool,
+    pub heatmap_overlay: bool,
+    // Where to write SVG images of the compared layouts and the best one found
+    pub svg: Option<PathBuf>,
+    // Extra layouts to compare against the built-in ones
+    pub layouts: Vec<Keyboard>,
+    // The layout the optimiser starts from, shuffling everything but its pinned keys
+    pub template: Keyboard,
+    pub export: Option<(Format, Keyboard)>,
+    pub optimiser: Optimiser,
+    // Runs with the same seed, corpus and settings find the same layouts
+    pub seed: u64,
+    pub threads: usize,
+    pub budget: Budget,
+    // Where to save the search every so often, and a checkpoint to carry on from
+    pub checkpoint: Option<PathBuf>,
+    pub resume: Option<PathBuf>,
+}
+
+// When to stop searching. With none of these set, the search runs until it's
+// interrupted.
+#[derive(Default)]
+pub struct Budget {
+    pub time: Option<Duration>,
+    pub evaluations: Option<u64>,
+    // Stop once the best layout hasn't improved for this long
+    pub converge: Option<Duration>,
+}
+
+impl Budget {
+    // Why the search should stop, if it should
+    pub fn exhausted(
+        &self,
+        elapsed: Duration,
+        evaluations: u64,
+        since_best: Duration,
+    ) -> Option<String> {
+        if let Some(time) = self.time.filter(|time| elapsed >= *time) {
+            return Some(format!(
+                "Used up the time budget of {}s",
+                time.as_secs_f64()
+            ));
+        }
+        if let Some(limit) = self.evaluations.filter(|limit| evaluations >= *limit) {
+            return Some(format!("Used up the budget of {} evaluations", limit));
+        }
+        if let Some(converge) = self.converge.filter(|converge| since_best >= *converge) {
+            return Some(format!(
+                "Converged, no better layout found in {}s",
+                converge.as_secs_f64()
+            ));
+        }
+        None
+    }
+}
+
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
+
+fn next_value(args: &mut impl Iterator<Item = String>, flag: &str, what: &str) -> String {
+    match args.next() {
+        Some(value) => value,
+        None => fail(format!("{} needs {}", flag, what)),
+    }
+}
+
+fn next_number<T: FromStr>(args: &mut impl Iterator<Item = String>, flag: &str, what: &str) -> T {
+    let value = next_value(args, flag, what);
+    value
+        .parse()
+        .unwrap_or_else(|_| fail(format!("{} needs {}, not '{}'", flag, what, value)))
+}
+
+fn next_seconds(args: &mut impl Iterator<Item = String>, flag: &str) -> Duration {
+    let seconds: f64 = next_number(args, flag, "a number of seconds");
+    Duration::try_from_secs_f64(seconds)
+        .unwrap_or_else(|_| fail(format!("{} needs a positive number of seconds", flag)))
+}
+
+pub fn parse_args() -> Options {
+    let mut options = Options {
+        corpus: vec![],
+        debug: false,
+        breakdown: false,
+        heatmap: false,
+        heatmap_overlay: false,
+        svg: None,
+        layouts: vec![],
+        template: Keyboard::qwerty(),
+        export: None,
+        optimiser: Optimiser::HillClimb,
+        seed: rand::thread_rng().gen(),
+        threads: WORKER_THREADS,
+        budget: Budget::default(),
+        checkpoint: None,
+        resume: None,
+    };
+    let mut template = None;
+    // Characters that keep their qwerty key when exporting
+    let mut keep = String::new();
+
+    // The flags that set up the search, which a resumed search takes from its
+    // checkpoint instead
+    let mut set_up = vec![];
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if matches!(
+            arg.as_str(),
+            "--template" | "--optimiser" | "--seed" | "--threads"
+        ) {
+            set_up.push(arg.clone());
+        }
+
+        match arg.as_str() {
+            "--debug" => options.debug = true,
+            "--breakdown" => options.breakdown = true,
+            "--heatmap" => options.heatmap = true,
+            "--heatmap-overlay" => {
+                options.heatmap = true;
+                options.heatmap_overlay = true;
+            }
+            "--svg" => {
+                let dir = next_value(&mut args, &arg, "the path of a directory");
+                if let Err(e) = std::fs::create_dir_all(&dir) {
+                    fail(format!("{}: couldn't create directory: {}", dir, e));
+                }
+                options.svg = Some(PathBuf::from(dir));
+            }
+            "--layout" => {
+                let path = next_value(&mut args, &arg, "the path of a layout file");
+                options
+                    .layouts
+                    .push(layout::load_layout(Path::new(&path)).unwrap_or_else(|e| fail(e)));
+            }
+            "--layouts" => {
+                let dir = next_value(&mut args, &arg, "the path of a directory of layout files");
+                let mut layouts =
+                    layout::load_layout_dir(Path::new(&dir)).unwrap_or_else(|e| fail(e));
+                options.layouts.append(&mut layouts);
+            }
+            "--template" => {
+                let path = next_value(&mut args, &arg, "the path of a layout file");
+                template = Some(layout::load_layout(Path::new(&path)).unwrap_or_else(|e| fail(e)));
+            }
+            "--optimiser" => {
+                let name = next_value(&mut args, &arg, "the name of an optimiser");
+                let Some(optimiser) = Optimiser::from_name(&name) else {
+                    let known: Vec<_> = Optimiser::ALL.iter().map(|o| o.name()).collect();
+                    fail(format!(
+                        "unknown optimiser '{}', expected one of: {}",
+                        name,
+                        known.join(", ")
+                    ));
+                };
+                options.optimiser = optimiser;
+            }
+            "--seed" => options.seed = next_number(&mut args, &arg, "a whole number"),
+            "--threads" => {
+                options.threads = next_number(&mut args, &arg, "a number of threads");
+                if options.threads == 0 {
+                    fail(format!("{} needs at least one thread", arg));
+                }
+            }
+            "--time" => options.budget.time = Some(next_seconds(&mut args, &arg)),
+            "--evaluations" => {
+                options.budget.evaluations =
+                    Some(next_number(&mut args, &arg, "a number of evaluations"))
+            }
+            "--converge" => options.budget.converge = Some(next_seconds(&mut args, &arg)),
+            "--checkpoint" => {
+                let path = next_value(&mut args, &arg, "the path of a checkpoint file");
+                options.checkpoint = Some(PathBuf::from(path));
+            }
+            "--resume" => {
+                let path = next_value(&mut args, &arg, "the path of a checkpoint file");
+                options.resume = Some(PathBuf::from(path));
+            }
+            "--export" => {
+                let format = next_value(&mut args, &arg, "a format and a layout");
+                let Some(format) = Format::from_name(&format) else {
+                    let known: Vec<_> = Format::ALL.iter().map(|f| f.name()).collect();
+                    fail(format!(
+                        "unknown export format '{}', expected one of: {}",
+                        format,
+                        known.join(", ")
+                    ));
+                };
+                let kb = next_value(&mut args, &arg, "a format and a layout");
+                let kb = layout::parse_layout_arg(&kb).unwrap_or_else(|e| fail(e));
+
+                options.export = Some((format, kb));
+            }
+            "--keep" => keep = next_value(&mut args, &arg, "the characters to keep"),
+            _ => options.corpus.push(arg),
+        }
+    }
+
+    if options.resume.is_some() && !set_up.is_empty() {
+        fail(format!(
+            "--resume carries on with the search as its checkpoint set it up, so it can't be given with {}",
+            set_up.join(", ")
+        ));
+    }
+
+    if let Some((_, kb)) = &mut options.export {
+        *kb = keep_keys(kb, &keep).unwrap_or_else(|e| fail(e));
+    }
+
+    // Without a template, start from a random layout, picked by the seed too
+    options.template = template
+        .unwrap_or_else(|| Keyboard::random_layout(&mut StdRng::seed_from_u64(options.seed)));
+
+    options
+}
@@ -0,0 +1,241 @@
+// Drawing a layout on its geometry as an SVG image, for sharing outside the
+// terminal
+//
+// Keys are shaded by how often they're pressed, the most frequent same finger
+// bigrams and rolls are drawn as arcs between their keys, and each finger's
+// load is written under the keys it covers.
+
+use std::fmt::Write;
+
+use crate::{Keyboard, Scorer, FINGER_MAP, SVG_TOP_BIGRAMS};
+
+// The size of one key width, in pixels
+const UNIT: f64 = 60.0;
+const KEY_GAP: f64 = 4.0;
+const MARGIN: f64 = 20.0;
+// Room under the keys for the finger load
+const FOOTER: f64 = 40.0;
+
+// Keys are shaded from the first colour for the least pressed to the second
+// for the most pressed
+const COLD: (f64, f64, f64) = (242.0, 240.0, 230.0);
+const HOT: (f64, f64, f64) = (215.0, 48.0, 31.0);
+
+const SAME_FINGER_COLOUR: &str = "#b2182b";
+const ROLL_COLOUR: &str = "#2166ac";
+
+fn shade(heat: f64) -> String {
+    let mix = |cold: f64, hot: f64| (cold + (hot - cold) * heat).round() as u8;
+    format!(
+        "#{:02x}{:02x}{:02x}",
+        mix(COLD.0, HOT.0),
+        mix(COLD.1, HOT.1),
+        mix(COLD.2, HOT.2)
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+// Arcs between the centres of the keys of each bigram, thicker for the more
+// frequent ones
+fn arcs(
+    output: &mut String,
+    centres: &[Vec<(f64, f64)>],
+    kb: &Keyboard,
+    bigrams: &[(String, f64)],
+    colour: &str,
+) {
+    let Some(most) = bigrams.first().map(|(_, percentage)| *percentage) else {
+        return;
+    };
+
+    let positions = kb.positions();
+    let centre = |key: u8| {
+        let position = positions[key as usize] as usize;
+        centres[position / 10][position % 10]
+    };
+
+    for (bigram, percentage) in bigrams {
+        let bytes = bigram.as_bytes();
+        let (from, to) = (centre(bytes[0]), centre(bytes[1]));
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let length = (dx * dx + dy * dy).sqrt().max(1.0);
+
+        // Start and end a little away from the centres, to keep the legends clear
+        let inset = UNIT * 0.25;
+        let from = (from.0 + dx / length * inset, from.1 + dy / length * inset);
+        let to = (to.0 - dx / length * inset, to.1 - dy / length * inset);
+
+        // Bend each arc to its left, so a bigram and its reverse don't overlap
+        let bend = UNIT * 0.3;
+        let control = (
+            (from.0 + to.0) / 2.0 + dy / length * bend,
+            (from.1 + to.1) / 2.0 - dx / length * bend,
+        );
+
+        let _ = writeln!(
+            output,
+            "  <path d=\"M {:.1} {:.1} Q {:.1} {:.1} {:.1} {:.1}\" fill=\"none\" stroke=\"{}\" \
+             stroke-width=\"{:.1}\" stroke-opacity=\"0.7\" marker-end=\"url(#arrow-{})\">\
+             <title>{} {:.2}%</title></path>",
+            from.0,
+            from.1,
+            control.0,
+            control.1,
+            to.0,
+            to.1,
+            colour,
+            1.0 + 5.0 * percentage / most,
+            &colour[1..],
+            bigram,
+            percentage
+        );
+    }
+}
+
+pub fn render_svg(scorer: &Scorer, kb: &Keyboard) -> String {
+    let key_positions = kb.geometry.key_positions();
+
+    let max_x = key_positions
+        .iter()
+        .flatten()
+        .map(|p| p.0)
+        .fold(0.0, f32::max) as f64;
+    let max_y = key_positions
+        .iter()
+        .flatten()
+        .map(|p| p.1)
+        .fold(0.0, f32::max) as f64;
+    let width = (max_x + 1.0) * UNIT + 2.0 * MARGIN;
+    let height = (max_y + 1.0) * UNIT + 2.0 * MARGIN + FOOTER;
+
+    let frequencies: Vec<Vec<f64>> = kb
+        .rows
+        .iter()
+        .map(|keys| keys.iter().map(|key| scorer.key_frequency(*key)).collect())
+        .collect();
+    let hottest = frequencies.iter().flatten().copied().fold(0.0, f64::max);
+
+    let centres: Vec<Vec<(f64, f64)>> = key_positions
+        .iter()
+        .map(|keys| {
+            keys.iter()
+                .map(|(x, y)| {
+                    (
+                        MARGIN + (*x as f64 + 0.5) * UNIT,
+                        MARGIN + (*y as f64 + 0.5) * UNIT,
+                    )
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut output = String::new();
+
+    let _ = writeln!(
+        output,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.0}\" height=\"{:.0}\" \
+         viewBox=\"0 0 {:.0} {:.0}\" font-family=\"sans-serif\">",
+        width, height, width, height
+    );
+    let _ = writeln!(output, "  <title>{}</title>", escape(&kb.name));
+    let _ = writeln!(output, "  <defs>");
+    for colour in [SAME_FINGER_COLOUR, ROLL_COLOUR] {
+        let _ = writeln!(
+            output,
+            "    <marker id=\"arrow-{}\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" \
+             markerWidth=\"4\" markerHeight=\"4\" orient=\"auto-start-reverse\">\
+             <path d=\"M 0 0 L 10 5 L 0 10 z\" fill=\"{}\"/></marker>",
+            &colour[1..],
+            colour
+        );
+    }
+    let _ = writeln!(output, "  </defs>");
+    let _ = writeln!(
+        output,
+        "  <rect width=\"100%\" height=\"100%\" fill=\"white\"/>"
+    );
+
+    for (row, keys) in kb.rows.iter().enumerate() {
+        for (col, key) in keys.iter().enumerate() {
+            let (centre_x, centre_y) = centres[row][col];
+            let frequency = frequencies[row][col];
+            let heat = if hottest > 0.0 {
+                frequency / hottest
+            } else {
+                0.0
+            };
+            let size = UNIT - KEY_GAP;
+
+            let _ = writeln!(
+                output,
+                "  <rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" rx=\"6\" \
+                 fill=\"{}\" stroke=\"#888\"/>",
+                centre_x - size / 2.0,
+                centre_y - size / 2.0,
+                size,
+                size,
+                shade(heat)
+            );
+            if *key != b'_' {
+                let _ = writeln!(
+                    output,
+                    "  <text x=\"{:.1}\" y=\"{:.1}\" font-size=\"20\" text-anchor=\"middle\">{}</text>",
+                    centre_x,
+                    centre_y + 2.0,
+                    escape(&(*key as char).to_string())
+                );
+                let _ = writeln!(
+                    output,
+                    "  <text x=\"{:.1}\" y=\"{:.1}\" font-size=\"10\" text-anchor=\"middle\" \
+                     fill=\"#444\">{:.1}%</text>",
+                    centre_x,
+                    centre_y + 18.0,
+                    frequency
+                );
+            }
+        }
+    }
+
+    let same_finger: Vec<_> = scorer
+        .same_finger_bigrams(kb)
+        .into_iter()
+        .take(SVG_TOP_BIGRAMS)
+        .collect();
+    let rolls: Vec<_> = scorer
+        .roll_bigrams(kb)
+        .into_iter()
+        .take(SVG_TOP_BIGRAMS)
+        .collect();
+    arcs(&mut output, &centres, kb, &rolls, ROLL_COLOUR);
+    arcs(&mut output, &centres, kb, &same_finger, SAME_FINGER_COLOUR);
+
+    // Each finger's load, under the middle of the columns it covers
+    let load = scorer.finger_load(kb);
+    let bottom = centres[2].iter().map(|c| c.1).fold(0.0, f64::max) + UNIT / 2.0;
+    for (finger, percentage) in load.fingers.iter().enumerate() {
+        let columns: Vec<f64> = (0..10)
+            .filter(|col| FINGER_MAP[*col] == finger)
+            .map(|col| centres[2][col].0)
+            .collect();
+        let x = columns.iter().sum::<f64>() / columns.len() as f64;
+
+        let _ = writeln!(
+            output,
+            "  <text x=\"{:.1}\" y=\"{:.1}\" font-size=\"12\" text-anchor=\"middle\">{:.1}%</text>",
+            x,
+            bottom + 24.0,
+            percentage
+        );
+    }
+
+    let _ = writeln!(output, "</svg>");
+
+    output
+}